@@ -10,7 +10,7 @@ use rand_distr::Distribution;
 
 use eframe::egui;
 
-use egui_samplers::{TimeseriesLine, TimeseriesPlot, TimeseriesPlotMemory};
+use egui_oszi::{GapThreshold, TimeseriesLine, TimeseriesPlot, TimeseriesPlotMemory};
 
 // ~1kHz of garbage
 const SAMPLE_RATE: f64 = 1.0e3;
@@ -24,7 +24,7 @@ struct NoiseExample {
     first_frame: Instant,
     last_frame: Instant,
     // state for our plot widget
-    plot_memory: TimeseriesPlotMemory<Instant>,
+    plot_memory: TimeseriesPlotMemory<Instant, f32>,
 }
 
 impl NoiseExample {
@@ -96,7 +96,10 @@ impl eframe::App for NoiseExample {
             let timeseries = TimeseriesPlot::new(&mut self.plot_memory)
                 // Give an iterator over all values to be plotted
                 .line(
-                    TimeseriesLine::new("Ferrisses").unit("M🦀/s"),
+                    TimeseriesLine::new("Ferrisses")
+                        .unit("M🦀/s")
+                        // Show where the sensor clipped, instead of connecting the dots.
+                        .gap_threshold(GapThreshold::MedianInterval(3.0)),
                     self.sensor_data.iter().map(|(t, y)| (*t, *y)),
                 );
             // That's it.
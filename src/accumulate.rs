@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+/// How triggered segments are combined into the displayed trace, see
+/// [crate::TimeseriesPlotMemory::set_accumulation].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Accumulation {
+    /// Show the average of the last N segments.
+    Average(usize),
+    /// Show the minimum and maximum at every position over the last N
+    /// segments.
+    Envelope(usize),
+    /// Show the minimum and maximum at every position over all segments
+    /// since the accumulation was reset.
+    PeakHold,
+}
+
+/// Combines segments of a line around trigger events. Segments are resampled
+/// at fixed positions relative to the trigger event, so they line up even if
+/// the trigger event falls between samples.
+#[derive(Debug)]
+pub(crate) struct Accumulator {
+    accumulation: Accumulation,
+    // Range on the X axis relative to the trigger event.
+    pub(crate) window: (f64, f64),
+    // Number of positions, taken from the number of samples in the first
+    // segment.
+    len: usize,
+    segments: VecDeque<Vec<f64>>,
+    // Sum and number of values at every position, for averaging.
+    sums: Vec<f64>,
+    counts: Vec<usize>,
+    min: Vec<f64>,
+    max: Vec<f64>,
+}
+
+impl Accumulator {
+    pub(crate) fn new(accumulation: Accumulation, window: (f64, f64), len: usize) -> Self {
+        let len = len.max(2);
+        Self {
+            accumulation,
+            window,
+            len,
+            segments: VecDeque::new(),
+            sums: vec![0.0; len],
+            counts: vec![0; len],
+            min: vec![f64::NAN; len],
+            max: vec![f64::NAN; len],
+        }
+    }
+
+    /// The X axis value of each position, relative to the trigger event.
+    pub(crate) fn positions(&self) -> impl Iterator<Item = f64> {
+        let (start, end) = self.window;
+        let step = (end - start) / (self.len - 1) as f64;
+        (0..self.len).map(move |i| start + i as f64 * step)
+    }
+
+    /// Adds a segment, given as a function returning the value at an X axis
+    /// value relative to the trigger event. Missing values are skipped.
+    pub(crate) fn add(&mut self, value_at: impl Fn(f64) -> Option<f64>) {
+        let segment: Vec<f64> = self
+            .positions()
+            .map(|x| value_at(x).unwrap_or(f64::NAN))
+            .collect();
+
+        match self.accumulation {
+            Accumulation::Average(n) => {
+                for (i, y) in segment.iter().enumerate().filter(|(_, y)| !y.is_nan()) {
+                    self.sums[i] += y;
+                    self.counts[i] += 1;
+                }
+                self.segments.push_back(segment);
+                while self.segments.len() > n.max(1) {
+                    let oldest = self.segments.pop_front().unwrap();
+                    for (i, y) in oldest.iter().enumerate().filter(|(_, y)| !y.is_nan()) {
+                        self.sums[i] -= y;
+                        self.counts[i] -= 1;
+                    }
+                }
+            }
+            Accumulation::Envelope(n) => {
+                self.segments.push_back(segment);
+                while self.segments.len() > n.max(1) {
+                    self.segments.pop_front();
+                }
+                self.min.fill(f64::NAN);
+                self.max.fill(f64::NAN);
+                for segment in &self.segments {
+                    Self::include(&mut self.min, &mut self.max, segment);
+                }
+            }
+            Accumulation::PeakHold => Self::include(&mut self.min, &mut self.max, &segment),
+        }
+    }
+
+    // f64::min and f64::max ignore NaN, which marks missing values.
+    fn include(min: &mut [f64], max: &mut [f64], segment: &[f64]) {
+        for (i, y) in segment.iter().enumerate() {
+            min[i] = min[i].min(*y);
+            max[i] = max[i].max(*y);
+        }
+    }
+
+    /// The traces to be drawn: the average, or the minimum and maximum.
+    /// Missing values are NaN.
+    pub(crate) fn traces(&self) -> Vec<Vec<f64>> {
+        match self.accumulation {
+            Accumulation::Average(_) => vec![self
+                .sums
+                .iter()
+                .zip(&self.counts)
+                .map(|(sum, count)| match count {
+                    0 => f64::NAN,
+                    count => sum / *count as f64,
+                })
+                .collect()],
+            Accumulation::Envelope(_) | Accumulation::PeakHold => {
+                vec![self.min.clone(), self.max.clone()]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Compares traces, treating NaN as equal to NaN.
+    fn assert_traces(actual: Vec<Vec<f64>>, expected: Vec<Vec<f64>>) {
+        let same = |a: &f64, b: &f64| a == b || a.is_nan() && b.is_nan();
+        assert!(
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(&expected)
+                    .all(|(a, b)| a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn average_slides_over_the_last_segments() {
+        let mut accumulator = Accumulator::new(Accumulation::Average(3), (0.0, 4.0), 5);
+        assert_eq!(
+            accumulator.positions().collect::<Vec<_>>(),
+            [0.0, 1.0, 2.0, 3.0, 4.0]
+        );
+
+        // Segment k is 10 * k + x, missing the middle position in every other
+        // segment and the last position from segment 3 on.
+        let segment = |k: usize, x: f64| -> Option<f64> {
+            let missing = (x == 2.0 && k % 2 == 1) || (x == 4.0 && k >= 3);
+            (!missing).then_some(10.0 * k as f64 + x)
+        };
+        for k in 0..7 {
+            accumulator.add(|x| segment(k, x));
+            let expected = accumulator
+                .positions()
+                .map(|x| {
+                    let values: Vec<f64> = (k.saturating_sub(2)..=k)
+                        .filter_map(|k| segment(k, x))
+                        .collect();
+                    match values.len() {
+                        0 => f64::NAN,
+                        n => values.iter().sum::<f64>() / n as f64,
+                    }
+                })
+                .collect();
+            assert_traces(accumulator.traces(), vec![expected]);
+        }
+    }
+
+    #[test]
+    fn envelope_drops_old_segments() {
+        let mut accumulator = Accumulator::new(Accumulation::Envelope(2), (0.0, 1.0), 2);
+        let expected = [(5.0, 5.0), (1.0, 5.0), (1.0, 3.0), (2.0, 3.0)];
+        for (y, (min, max)) in [5.0, 1.0, 3.0, 2.0].into_iter().zip(expected) {
+            accumulator.add(|_| Some(y));
+            assert_traces(accumulator.traces(), vec![vec![min; 2], vec![max; 2]]);
+        }
+    }
+
+    #[test]
+    fn peak_hold_keeps_the_extremes_of_all_segments() {
+        let mut accumulator = Accumulator::new(Accumulation::PeakHold, (0.0, 1.0), 2);
+        accumulator.add(|_| None);
+        assert_traces(accumulator.traces(), vec![vec![f64::NAN; 2]; 2]);
+
+        let expected = [(5.0, 5.0), (1.0, 5.0), (1.0, 5.0), (1.0, 5.0)];
+        for (y, (min, max)) in [5.0, 1.0, 3.0, 2.0].into_iter().zip(expected) {
+            accumulator.add(|_| Some(y));
+            assert_traces(accumulator.traces(), vec![vec![min; 2], vec![max; 2]]);
+        }
+
+        // Missing values don't affect the extremes.
+        accumulator.add(|x| (x == 0.0).then_some(-1.0));
+        assert_traces(accumulator.traces(), vec![vec![-1.0, 1.0], vec![5.0, 5.0]]);
+    }
+}
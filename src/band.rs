@@ -0,0 +1,75 @@
+use std::ops::RangeInclusive;
+
+use egui::epaint::Mesh;
+use egui::{Color32, Shape, Ui};
+use egui_plot::{PlotBounds, PlotGeometry, PlotItem, PlotPoint, PlotTransform};
+
+/// A filled band between a lower and upper value, given as `[x, min, _,
+/// max]` rows like returned by [crate::TimeseriesPlotMemory::plot_band]. NaN
+/// rows interrupt the band.
+///
+/// Unlike [egui_plot::Polygon], the band doesn't have to be convex, and it is
+/// drawn as a single triangle strip without any tessellation.
+pub(crate) struct BandItem {
+    rows: Vec<[f64; 4]>,
+    color: Color32,
+}
+
+impl BandItem {
+    pub(crate) fn new(rows: Vec<[f64; 4]>, color: Color32) -> Self {
+        Self { rows, color }
+    }
+}
+
+impl PlotItem for BandItem {
+    fn shapes(&self, _ui: &Ui, transform: &PlotTransform, shapes: &mut Vec<Shape>) {
+        let mut mesh = Mesh::default();
+        for run in self.rows.split(|r| r[1].is_nan()) {
+            for (i, row) in run.iter().enumerate() {
+                let index = mesh.vertices.len() as u32;
+                for y in [row[1], row[3]] {
+                    let pos = transform.position_from_point(&PlotPoint::new(row[0], y));
+                    mesh.colored_vertex(pos, self.color);
+                }
+                if i > 0 {
+                    mesh.add_triangle(index - 2, index - 1, index);
+                    mesh.add_triangle(index - 1, index, index + 1);
+                }
+            }
+        }
+        shapes.push(Shape::mesh(mesh));
+    }
+
+    fn initialize(&mut self, _x_range: RangeInclusive<f64>) {}
+
+    fn name(&self) -> &str {
+        ""
+    }
+
+    fn color(&self) -> Color32 {
+        self.color
+    }
+
+    fn highlight(&mut self) {}
+
+    fn highlighted(&self) -> bool {
+        false
+    }
+
+    fn geometry(&self) -> PlotGeometry<'_> {
+        PlotGeometry::None
+    }
+
+    fn bounds(&self) -> PlotBounds {
+        let mut bounds = PlotBounds::NOTHING;
+        for row in self.rows.iter().filter(|r| !r[1].is_nan()) {
+            bounds.extend_with(&PlotPoint::new(row[0], row[1]));
+            bounds.extend_with(&PlotPoint::new(row[0], row[3]));
+        }
+        bounds
+    }
+
+    fn id(&self) -> Option<egui::Id> {
+        None
+    }
+}
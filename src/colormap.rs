@@ -0,0 +1,50 @@
+use egui::Color32;
+
+/// Maps intensities to colors, for [crate::Persistence] and density plots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    /// Shades of green fading to white, like the phosphor of an analog
+    /// oscilloscope.
+    #[default]
+    Phosphor,
+    /// Black to red, yellow and white.
+    Heat,
+    /// Perceptually uniform blue to green to yellow.
+    Viridis,
+    Grayscale,
+}
+
+impl Colormap {
+    fn stops(&self) -> &'static [[u8; 3]] {
+        match self {
+            Colormap::Phosphor => &[[0, 40, 0], [0, 160, 40], [60, 255, 90], [230, 255, 230]],
+            Colormap::Heat => &[[40, 0, 0], [200, 30, 0], [255, 200, 0], [255, 255, 255]],
+            Colormap::Viridis => &[
+                [68, 1, 84],
+                [59, 82, 139],
+                [33, 145, 140],
+                [94, 201, 98],
+                [253, 231, 37],
+            ],
+            Colormap::Grayscale => &[[40, 40, 40], [255, 255, 255]],
+        }
+    }
+
+    /// Returns the color for an intensity between 0 and 1. Zero is fully
+    /// transparent, so empty areas don't cover the plot.
+    pub fn color(&self, intensity: f32) -> Color32 {
+        if intensity.is_nan() || intensity <= 0.0 {
+            return Color32::TRANSPARENT;
+        }
+
+        let stops = self.stops();
+        let position = intensity.min(1.0) * (stops.len() - 1) as f32;
+        let i = (position as usize).min(stops.len() - 2);
+        let t = position - i as f32;
+        let [r, g, b] = [0, 1, 2].map(|c| {
+            let (from, to) = (stops[i][c] as f32, stops[i + 1][c] as f32);
+            (from + (to - from) * t).round() as u8
+        });
+        Color32::from_rgb(r, g, b)
+    }
+}
@@ -0,0 +1,184 @@
+use egui::{Align2, CursorIcon, FontId, Rect, Response, Stroke, Ui, Vec2};
+use egui_plot::PlotTransform;
+
+// How close the pointer has to be to a cursor to grab it, in points.
+const GRAB_DISTANCE: f32 = 6.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Handle {
+    Time(usize),
+    Value(usize),
+}
+
+/// State of the draggable measurement cursors, kept in
+/// [crate::TimeseriesPlotMemory].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Cursors {
+    // Positions of the two time cursors on the X axis, as displayed.
+    pub(crate) time: Option<[f64; 2]>,
+    // Positions of the two level cursors on the Y axis.
+    pub(crate) value: Option<[f64; 2]>,
+    hovered: Option<Handle>,
+    dragged: Option<Handle>,
+}
+
+impl Cursors {
+    /// Whether a cursor is under the pointer or being dragged, in which case
+    /// the plot should not be panned.
+    pub(crate) fn grabbed(&self) -> bool {
+        self.hovered.is_some() || self.dragged.is_some()
+    }
+
+    /// Places the requested cursors in the current view if they haven't been
+    /// yet, moves them while dragged and paints them on top of the plot.
+    ///
+    /// The cursors are not added as plot items, so they don't affect the
+    /// automatic plot bounds.
+    pub(crate) fn show(
+        &mut self,
+        ui: &Ui,
+        response: &Response,
+        transform: &PlotTransform,
+        show_time: bool,
+        show_value: bool,
+    ) {
+        let bounds = transform.bounds();
+        let thirds = |min: f64, size: f64| [min + size / 3.0, min + size * 2.0 / 3.0];
+        let mut handles = Vec::new();
+        if show_time {
+            self.time
+                .get_or_insert_with(|| thirds(bounds.min()[0], bounds.width()));
+            handles.extend([Handle::Time(0), Handle::Time(1)]);
+        }
+        if show_value {
+            self.value
+                .get_or_insert_with(|| thirds(bounds.min()[1], bounds.height()));
+            handles.extend([Handle::Value(0), Handle::Value(1)]);
+        }
+
+        if response.drag_started() {
+            self.dragged = self.hovered;
+        } else if !response.dragged() {
+            self.dragged = None;
+        }
+        if let (Some(handle), Some(pos)) = (self.dragged, response.hover_pos()) {
+            let value = transform.value_from_position(pos);
+            match handle {
+                Handle::Time(i) => self.time.as_mut().map(|c| c[i] = value.x),
+                Handle::Value(i) => self.value.as_mut().map(|c| c[i] = value.y),
+            };
+        }
+
+        self.hovered = response.hover_pos().and_then(|pos| {
+            let distance = |handle: &Handle| match (handle, self.time, self.value) {
+                (Handle::Time(i), Some(time), _) => {
+                    (transform.position_from_point_x(time[*i]) - pos.x).abs()
+                }
+                (Handle::Value(i), _, Some(value)) => {
+                    (transform.position_from_point_y(value[*i]) - pos.y).abs()
+                }
+                _ => f32::INFINITY,
+            };
+            handles
+                .iter()
+                .filter(|h| distance(h) < GRAB_DISTANCE)
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                .copied()
+        });
+
+        let frame = *transform.frame();
+        let painter = ui.painter_at(frame);
+        let color = ui.visuals().text_color();
+        let stroke = Stroke::new(1.0, color.gamma_multiply(0.6));
+        let font = FontId::monospace(12.0);
+        for (i, x) in self.time.iter().flatten().enumerate().filter(|_| show_time) {
+            let x = transform.position_from_point_x(*x);
+            painter.vline(x, frame.y_range(), stroke);
+            painter.text(
+                egui::pos2(x + 3.0, frame.top() + 2.0),
+                Align2::LEFT_TOP,
+                format!("X{}", i + 1),
+                font.clone(),
+                color,
+            );
+        }
+        for (i, y) in self
+            .value
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|_| show_value)
+        {
+            let y = transform.position_from_point_y(*y);
+            painter.hline(frame.x_range(), y, stroke);
+            painter.text(
+                egui::pos2(frame.left() + 3.0, y - 2.0),
+                Align2::LEFT_BOTTOM,
+                format!("Y{}", i + 1),
+                font.clone(),
+                color,
+            );
+        }
+    }
+
+    /// Shows a resize cursor while a cursor is grabbed.
+    pub(crate) fn set_cursor_icon(&self, ui: &Ui) {
+        match self.dragged.or(self.hovered) {
+            Some(Handle::Time(_)) => ui.ctx().set_cursor_icon(CursorIcon::ResizeHorizontal),
+            Some(Handle::Value(_)) => ui.ctx().set_cursor_icon(CursorIcon::ResizeVertical),
+            None => {}
+        }
+    }
+}
+
+/// Formats a measured value with four significant digits, followed by the
+/// unit if there is one.
+pub(crate) fn format_value(value: Option<f64>, unit: Option<&str>) -> String {
+    let value = match value {
+        Some(v) if v.is_finite() && v != 0.0 => {
+            let decimals = (3.0 - v.abs().log10().floor()).clamp(0.0, 12.0) as usize;
+            format!("{:.*}", decimals, v)
+        }
+        Some(v) if v.is_finite() => "0".to_string(),
+        _ => return "–".to_string(),
+    };
+    match unit {
+        Some(unit) => format!("{} {}", value, unit),
+        None => value,
+    }
+}
+
+/// Paints the given text in a corner of the plot frame, on top of the plot.
+pub(crate) fn paint_readout(ui: &Ui, frame: Rect, corner: Align2, text: String) {
+    let visuals = ui.visuals();
+    let painter = ui.painter_at(frame);
+    let galley = painter.layout_no_wrap(text, FontId::monospace(12.0), visuals.text_color());
+    let padding = Vec2::splat(4.0);
+    let anchor = corner.pos_in_rect(&frame.shrink(8.0));
+    let rect = corner.anchor_size(anchor, galley.size()).expand2(padding);
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color.gamma_multiply(0.8));
+    painter.galley(rect.min + padding, galley, visuals.text_color());
+}
+
+/// Lays out the given rows as a table with left aligned columns.
+pub(crate) fn format_table(rows: &[Vec<String>]) -> String {
+    let mut widths = Vec::new();
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,58 @@
+use egui::ColorImage;
+use egui_plot::PlotBounds;
+
+use crate::colormap::Colormap;
+use crate::texture::CachedTexture;
+
+/// Counts the points in each of `width` × `height` bins covering the given
+/// plot bounds, row by row from the top. Gaps are skipped.
+pub(crate) fn histogram<Y: Copy + Into<f64>>(
+    points: &[(f64, Y)],
+    bounds: PlotBounds,
+    [width, height]: [usize; 2],
+) -> Vec<u32> {
+    let mut counts = vec![0; width * height];
+    let (min, max) = (bounds.min(), bounds.max());
+    let (x_scale, y_scale) = (
+        width as f64 / bounds.width(),
+        height as f64 / bounds.height(),
+    );
+    for (x, y) in points {
+        let y: f64 = (*y).into();
+        let column = (x - min[0]) * x_scale;
+        let row = (max[1] - y) * y_scale;
+        if (0.0..width as f64).contains(&column) && (0.0..height as f64).contains(&row) {
+            counts[row as usize * width + column as usize] += 1;
+        }
+    }
+    counts
+}
+
+/// Colors a histogram on a logarithmic scale, so sparse outliers remain
+/// visible next to bins with millions of points.
+pub(crate) fn to_image(
+    counts: &[u32],
+    [width, height]: [usize; 2],
+    colormap: Colormap,
+) -> ColorImage {
+    let max = counts.iter().copied().max().unwrap_or_default().max(1);
+    let scale = 1.0 / (max as f32).ln_1p();
+    ColorImage {
+        size: [width, height],
+        pixels: counts
+            .iter()
+            .map(|count| colormap.color((*count as f32).ln_1p() * scale))
+            .collect(),
+    }
+}
+
+// Plot bounds, size, colormap, and revision and end of the line data.
+type DensityKey = (PlotBounds, [usize; 2], Colormap, usize, Option<f64>);
+
+/// Texture of the density plot of a line, along with what it was computed
+/// from, so it is only recomputed when needed.
+#[derive(Debug, Default)]
+pub(crate) struct DensityState {
+    pub(crate) key: Option<DensityKey>,
+    pub(crate) texture: CachedTexture,
+}
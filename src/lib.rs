@@ -1,17 +1,64 @@
-use egui::{Color32, Response, Ui, Vec2, Vec2b};
-use egui_plot::{Legend, PlotPoints};
+use egui::{Align2, Color32, Response, Ui, Vec2, Vec2b};
+use egui_plot::{Legend, PlotBounds, PlotPoints, PlotTransform};
 
+mod accumulate;
+mod band;
+mod colormap;
+mod cursors;
+mod density;
+mod mask;
+mod math;
+mod measurement;
 mod memory;
+mod persistence;
+mod reference;
+mod segments;
+mod texture;
 mod traits;
+mod trigger;
 
+pub use accumulate::*;
+use band::BandItem;
+pub use colormap::*;
+use cursors::{format_table, format_value, paint_readout};
+pub use mask::*;
+pub use math::*;
+pub use measurement::*;
 pub use memory::*;
+pub use persistence::*;
+pub use segments::*;
+use texture::ImageItem;
 pub use traits::*;
+pub use trigger::*;
 
 #[derive(Default)]
 pub enum ViewMode {
     #[default]
     Complete,
-    AttachedToEdge(f64), // TODO: use X axis diff unit?
+    /// Follow the newest data, showing the given width in plot units.
+    AttachedToEdge(f64),
+    /// Keep a window of the given width in plot units fixed, and overwrite
+    /// the trace from left to right, wrapping around when it is full.
+    Sweep(f64),
+}
+
+// Width of the erased part of the trace in front of the write position in
+// sweep mode, as a fraction of the window.
+const SWEEP_GAP_FRACTION: f64 = 0.02;
+
+/// How the data of a [TimeseriesLine] is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    #[default]
+    Line,
+    /// Count the points falling into each pixel and draw the counts with the
+    /// given colormap, on a logarithmic scale. This shows how often values
+    /// occur, e.g. for noisy signals, instead of just their extremes.
+    Density(Colormap),
+    /// Draw downsampled data as a filled band between the minimum and
+    /// maximum of each pixel column, with the mean as centre line. When
+    /// zoomed in far enough to show raw data, the line is drawn as usual.
+    Band,
 }
 
 pub struct TimeseriesLine {
@@ -20,6 +67,9 @@ pub struct TimeseriesLine {
     unit: Option<String>,
     color: Option<Color32>,
     width: Option<f32>,
+    downsampling_method: DownsamplingMethod,
+    gap_threshold: GapThreshold,
+    render_mode: RenderMode,
 }
 
 impl TimeseriesLine {
@@ -32,6 +82,9 @@ impl TimeseriesLine {
             unit: None,
             color: None,
             width: None,
+            downsampling_method: DownsamplingMethod::default(),
+            gap_threshold: GapThreshold::default(),
+            render_mode: RenderMode::default(),
         }
     }
 
@@ -49,14 +102,40 @@ impl TimeseriesLine {
         self.unit = Some(unit.to_string());
         self
     }
+
+    /// Set how this line is downsampled when zoomed out. Defaults to
+    /// [DownsamplingMethod::MinMax].
+    pub fn downsampling(mut self, downsampling_method: DownsamplingMethod) -> Self {
+        self.downsampling_method = downsampling_method;
+        self
+    }
+
+    /// Interrupt the line where consecutive samples are too far apart, so
+    /// missing data is visible even without explicit `None` values.
+    pub fn gap_threshold(mut self, gap_threshold: GapThreshold) -> Self {
+        self.gap_threshold = gap_threshold;
+        self
+    }
+
+    /// Set how this line is drawn. Defaults to [RenderMode::Line].
+    pub fn render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
 }
 
 pub struct TimeseriesPlot<'mem, X, Y> {
     memory: &'mem mut TimeseriesPlotMemory<X, Y>,
-    group: Option<&'mem mut TimeseriesGroup>,
+    group: Option<&'mem mut TimeseriesGroup<X>>,
     plot: egui_plot::Plot,
     lines: Vec<TimeseriesLine>,
     view_mode: ViewMode,
+    trigger: Option<Trigger>,
+    time_cursors: bool,
+    value_cursors: bool,
+    measurements: Vec<Measurement>,
+    mask: Option<Mask>,
+    persistence: Option<Persistence>,
 }
 
 impl<
@@ -82,6 +161,12 @@ impl<
                 .legend(Legend::default().position(egui_plot::Corner::LeftTop)),
             lines: Vec::new(),
             view_mode: ViewMode::default(),
+            trigger: None,
+            time_cursors: false,
+            value_cursors: false,
+            measurements: Vec::new(),
+            mask: None,
+            persistence: None,
         }
     }
 
@@ -103,49 +188,478 @@ impl<
         self
     }
 
-    pub fn group(mut self, group: &'mem mut TimeseriesGroup) -> Self {
+    pub fn group(mut self, group: &'mem mut TimeseriesGroup<X>) -> Self {
+        self.memory.sync_origin(group);
         self.group = Some(group);
         self
     }
 
+    /// Set how many points are plotted per pixel of plot width before
+    /// downsampling kicks in. See [TimeseriesPlotMemory::set_points_per_pixel].
+    pub fn points_per_pixel(self, points_per_pixel: f32) -> Self {
+        self.memory.set_points_per_pixel(points_per_pixel);
+        self
+    }
+
     pub fn include_y(mut self, y: Y) -> Self {
         self.plot = self.plot.include_y(y);
         self
     }
 
-    // TODO: change unit ot x axis diff
-    pub fn follow_edge(mut self, duration: f64) -> Self {
-        self.view_mode = ViewMode::AttachedToEdge(duration);
+    /// Follow the newest data, showing the given duration. The duration can
+    /// still be changed by zooming.
+    pub fn follow_edge(mut self, duration: X::Difference) -> Self {
+        self.view_mode = ViewMode::AttachedToEdge(X::difference_to_f64(duration));
+        self
+    }
+
+    /// Show the data in a fixed window of the given duration, which is
+    /// overwritten from left to right by new data, like the sweep mode of an
+    /// oscilloscope. Unlike [TimeseriesPlot::follow_edge], only the write
+    /// position moves.
+    pub fn sweep(mut self, window: X::Difference) -> Self {
+        self.view_mode = ViewMode::Sweep(X::difference_to_f64(window));
+        self
+    }
+
+    /// Pin the view to the most recent event of the given trigger. The X axis
+    /// then shows the distance to the trigger event.
+    pub fn trigger(mut self, trigger: Trigger) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Combine the segments around consecutive trigger events, see
+    /// [TimeseriesPlotMemory::set_accumulation].
+    pub fn accumulation(self, accumulation: Option<Accumulation>) -> Self {
+        self.memory.set_accumulation(accumulation);
+        self
+    }
+
+    /// Accumulate the lines of every acquisition into a fading intensity
+    /// buffer, drawn behind or instead of the lines. Acquisitions are best
+    /// defined by a trigger, since the buffer starts over whenever the view
+    /// moves.
+    pub fn persistence(mut self, persistence: Persistence) -> Self {
+        self.persistence = Some(persistence);
+        self
+    }
+
+    /// Test every acquisition against the given mask, highlighting points
+    /// outside of it and counting passed and failed acquisitions in
+    /// [TimeseriesPlotMemory]. Masks relative to the trigger need a trigger.
+    pub fn mask(mut self, mask: Mask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Show two draggable vertical cursors, with a readout of their
+    /// positions, the distance between them, and the value of every line at
+    /// each cursor. The positions are kept in [TimeseriesPlotMemory].
+    pub fn time_cursors(mut self, show: bool) -> Self {
+        self.time_cursors = show;
+        self
+    }
+
+    /// Show two draggable horizontal cursors, with a readout of their levels
+    /// and the difference between them, in the unit of the lines.
+    pub fn value_cursors(mut self, show: bool) -> Self {
+        self.value_cursors = show;
+        self
+    }
+
+    /// Add a line that is computed from other lines of this plot, see
+    /// [TimeseriesPlotMemory::set_math_line]. The source lines are kept up
+    /// to date through [TimeseriesPlot::line] as usual, or through
+    /// [TimeseriesPlotMemory::update_cache] if they should not be drawn.
+    pub fn math_line(mut self, line: TimeseriesLine, function: MathFunction) -> Self {
+        self.memory.set_math_line(&line.id, function);
+        self.memory
+            .set_downsampling_method(&line.id, line.downsampling_method);
+        self.memory.set_gap_threshold(&line.id, line.gap_threshold);
+        self.lines.push(line);
+        self
+    }
+
+    /// Show a table with the given measurements for every line, taken
+    /// between the time cursors if they are shown, or over the visible data
+    /// otherwise. See [TimeseriesPlotMemory::measure].
+    pub fn measurements(mut self, measurements: &[Measurement]) -> Self {
+        self.measurements = measurements.to_vec();
         self
     }
 
     pub fn line<
         'draw,
         I: Iterator<Item = (X, Y)> + ExactSizeIterator + DoubleEndedIterator + 'draw,
+    >(
+        self,
+        line: TimeseriesLine,
+        iterator: I,
+    ) -> Self {
+        self.line_sparse(line, iterator.map(|(t, y)| (t, Some(y))))
+    }
+
+    /// Add a line with missing values. Wherever the iterator yields `None`,
+    /// the line is interrupted, at every zoom level.
+    pub fn line_sparse<
+        'draw,
+        I: Iterator<Item = (X, Option<Y>)> + ExactSizeIterator + DoubleEndedIterator + 'draw,
     >(
         mut self,
         line: TimeseriesLine,
         iterator: I,
     ) -> Self {
         self.memory
-            .update_cache(&line.id, iterator.map(|(t, y)| (t, Some(y))));
+            .set_downsampling_method(&line.id, line.downsampling_method);
+        self.memory.set_gap_threshold(&line.id, line.gap_threshold);
+        self.memory.update_cache(&line.id, iterator);
         self.lines.push(line);
         self
     }
+}
+
+/// Same colors as egui_plot would assign automatically. Since lines with gaps
+/// are drawn as multiple segments, we have to assign them ourselves.
+fn auto_color(index: usize) -> Color32 {
+    let golden_ratio = (5.0_f32.sqrt() - 1.0) / 2.0;
+    let h = index as f32 * golden_ratio;
+    egui::ecolor::Hsva::new(h, 0.85, 0.5, 1.0).into()
+}
+
+/// Adds a line to the plot. Gaps are marked by NaN values, at which the line
+/// is split into multiple segments.
+fn add_line(
+    plot_ui: &mut egui_plot::PlotUi,
+    points: &[[f64; 2]],
+    color: Color32,
+    label: Option<&str>,
+    width: Option<f32>,
+) {
+    for segment in points.split(|p| p[1].is_nan()).filter(|s| !s.is_empty()) {
+        let mut egui_line = egui_plot::Line::new(PlotPoints::new(segment.to_vec())).color(color);
+        if let Some(label) = label {
+            egui_line = egui_line.name(label);
+        }
+        if let Some(width) = width {
+            egui_line = egui_line.width(width);
+        }
+
+        plot_ui.line(egui_line);
+    }
+}
+
+/// Sections of a sweep through `window` wide parts of the X axis, with the
+/// newest data at `end`. The current sweep is drawn from the left up to the
+/// write position, followed by a gap and the rest of the previous sweep. Each
+/// section is a range on the X axis and the value drawn at zero.
+fn sweep_sections_at(end: f64, window: f64) -> Vec<(f64, f64, f64)> {
+    let start = (end / window).floor() * window;
+    let gap = window * SWEEP_GAP_FRACTION;
+    let mut sections = vec![(start, end, start)];
+    if end - window + gap < start {
+        sections.push((end - window + gap, start, start - window));
+    }
+    sections
+}
+
+// What is drawn in the plot: the plot bounds and width in pixels, and the
+// sections of the data shown, see [sweep_sections_at].
+struct View<'a> {
+    bounds: PlotBounds,
+    width: f32,
+    sections: &'a [(f64, f64, f64)],
+    // Whether data outside of the sections is dropped, as in sweep mode.
+    crop: bool,
+}
+
+impl View<'_> {
+    /// Bounds of the data in a section, and the width in pixels it is drawn
+    /// with.
+    fn section_bounds(&self, (x_min, x_max, _): (f64, f64, f64)) -> (PlotBounds, f32) {
+        let bounds =
+            PlotBounds::from_min_max([x_min, self.bounds.min()[1]], [x_max, self.bounds.max()[1]]);
+        let width = self.width * ((x_max - x_min) / self.bounds.width()) as f32;
+        (bounds, width)
+    }
+
+    /// Moves the given rows, starting with an X value, from a section to where
+    /// they are drawn.
+    fn place<const N: usize>(
+        &self,
+        (x_min, x_max, offset): (f64, f64, f64),
+        rows: impl IntoIterator<Item = [f64; N]>,
+    ) -> impl Iterator<Item = [f64; N]> {
+        let crop = self.crop;
+        rows.into_iter()
+            .filter(move |r| !crop || (x_min..=x_max).contains(&r[0]))
+            .map(move |mut r| {
+                r[0] -= offset;
+                r
+            })
+    }
+}
+
+/// The points of a line in all sections, separated by gaps.
+fn line_points<
+    X: TimeseriesXAxis,
+    Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+>(
+    memory: &mut TimeseriesPlotMemory<X, Y>,
+    line_id: &str,
+    view: &View,
+) -> Vec<[f64; 2]> {
+    let mut points = Vec::new();
+    for &section in view.sections {
+        if !points.is_empty() {
+            points.push([f64::NAN, f64::NAN]);
+        }
+        let (section_bounds, section_width) = view.section_bounds(section);
+        let section_points = memory.plot(line_id, section_bounds, section_width);
+        points.extend(view.place(section, section_points));
+    }
+    points
+}
+
+/// The band of a line in all sections, and its mean as the points of the
+/// line. Sections zoomed in to the raw data have no band, only points.
+fn band_points<
+    X: TimeseriesXAxis,
+    Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+>(
+    memory: &mut TimeseriesPlotMemory<X, Y>,
+    line_id: &str,
+    view: &View,
+) -> (Vec<[f64; 4]>, Vec<[f64; 2]>) {
+    let (mut band, mut points) = (Vec::new(), Vec::new());
+    for &section in view.sections {
+        if !points.is_empty() {
+            points.push([f64::NAN, f64::NAN]);
+        }
+        let (section_bounds, section_width) = view.section_bounds(section);
+        match memory.plot_band(line_id, section_bounds, section_width) {
+            Some(rows) => {
+                if !band.is_empty() {
+                    band.push([f64::NAN; 4]);
+                }
+                let start = band.len();
+                band.extend(view.place(section, rows));
+                points.extend(band[start..].iter().map(|r| [r[0], r[2]]));
+            }
+            None => {
+                let section_points = memory.plot(line_id, section_bounds, section_width);
+                points.extend(view.place(section, section_points));
+            }
+        }
+    }
+    (band, points)
+}
+
+/// Draws the density plot of a line, one image per section. The line itself
+/// is only added with zero width, for the automatic bounds and the legend.
+fn add_density<
+    X: TimeseriesXAxis,
+    Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+>(
+    plot_ui: &mut egui_plot::PlotUi,
+    memory: &mut TimeseriesPlotMemory<X, Y>,
+    line: &TimeseriesLine,
+    color: Color32,
+    view: &View,
+    colormap: Colormap,
+) {
+    let frame = plot_ui.transform().frame();
+    let height = (frame.height() * plot_ui.ctx().pixels_per_point()) as usize;
+    let ctx = plot_ui.ctx().clone();
+    for (i, &section) in view.sections.iter().enumerate() {
+        let (section_bounds, section_width) = view.section_bounds(section);
+        let Some(texture_id) = memory.plot_density(
+            &ctx,
+            &line.id,
+            i,
+            section_bounds,
+            [section_width as usize, height],
+            colormap,
+        ) else {
+            continue;
+        };
+        let (x_min, x_max, offset) = section;
+        let image_bounds = PlotBounds::from_min_max(
+            [x_min - offset, view.bounds.min()[1]],
+            [x_max - offset, view.bounds.max()[1]],
+        );
+        plot_ui.add(ImageItem::new(texture_id, image_bounds).legend(line.label.as_deref(), color));
+    }
+
+    let points = line_points(memory, &line.id, view);
+    add_line(plot_ui, &points, color, line.label.as_deref(), Some(0.0));
+}
+
+/// Adds the points of a line to its persistence buffer and draws it, at the
+/// resolution of the screen.
+fn add_persistence<
+    X: TimeseriesXAxis,
+    Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+>(
+    plot_ui: &mut egui_plot::PlotUi,
+    memory: &mut TimeseriesPlotMemory<X, Y>,
+    line: &TimeseriesLine,
+    color: Color32,
+    bounds: PlotBounds,
+    persistence: &Persistence,
+    points: &[[f64; 2]],
+) {
+    let frame = plot_ui.transform().frame();
+    let ppp = plot_ui.ctx().pixels_per_point();
+    let size = [
+        (frame.width() * ppp) as usize,
+        (frame.height() * ppp) as usize,
+    ];
+    let ctx = plot_ui.ctx().clone();
+    let (texture_id, image_bounds) =
+        memory.update_persistence(&ctx, &line.id, bounds, size, persistence, points);
+    plot_ui.add(ImageItem::new(texture_id, image_bounds).legend(line.label.as_deref(), color));
+}
+
+/// Draws the outline of a mask, along with the points that violated it in
+/// the last tested acquisition.
+fn add_mask<
+    X: TimeseriesXAxis,
+    Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+>(
+    plot_ui: &mut egui_plot::PlotUi,
+    memory: &TimeseriesPlotMemory<X, Y>,
+    mask: &Mask,
+    x_offset: f64,
+) {
+    // Masks relative to the trigger are drawn at zero, like the data, and
+    // their violations relative to the acquisition they were found in.
+    let state = memory.mask_state.as_ref();
+    let (shift, tested_at) = match mask.reference {
+        MaskReference::Trigger => (0.0, state.and_then(|s| s.tested_at).unwrap_or_default()),
+        MaskReference::Absolute => (-x_offset, x_offset),
+    };
+
+    let color = plot_ui.ctx().style().visuals.error_fg_color;
+    for chain in [&mask.upper, &mask.lower] {
+        let points: Vec<[f64; 2]> = chain.iter().map(|(x, y)| [x + shift, *y]).collect();
+        plot_ui.line(
+            egui_plot::Line::new(PlotPoints::new(points))
+                .color(color.gamma_multiply(0.6))
+                .style(egui_plot::LineStyle::dashed_dense()),
+        );
+    }
 
-    //pub fn line_sparse<
-    //    'b,
-    //    Y: Into<f64>,
-    //    I: TimeseriesIterator<X,Option<Y>> + Iterator<Item=(X,Option<Y>)> + ExactSizeIterator + DoubleEndedIterator + 'b
-    //>(
-    //    mut self,
-    //    line: TimeseriesLine,
-    //    iterator: I
-    //) -> Self {
-    //    self.memory.update_cache(line.id, iterator);
-    //    self.lines.push(line);
-    //    self
-    //}
+    let points: Vec<[f64; 2]> = state
+        .into_iter()
+        .flat_map(|s| &s.violations)
+        .map(|(x, y)| [x - tested_at, *y])
+        .collect();
+    if !points.is_empty() {
+        plot_ui.points(
+            egui_plot::Points::new(PlotPoints::new(points))
+                .color(color)
+                .radius(3.0),
+        );
+    }
+}
+
+/// Marks the trigger position on the X axis. This is painted on top of the
+/// plot, so it doesn't affect the automatic plot bounds.
+fn paint_trigger_marker(ui: &Ui, transform: &PlotTransform) {
+    let frame = transform.frame();
+    let x = transform.position_from_point_x(0.0);
+    if frame.x_range().contains(x) {
+        let size = 6.0;
+        let color = ui.visuals().text_color();
+        ui.painter_at(*frame).add(egui::Shape::convex_polygon(
+            vec![
+                egui::pos2(x, frame.bottom() - size),
+                egui::pos2(x + size, frame.bottom()),
+                egui::pos2(x - size, frame.bottom()),
+            ],
+            color,
+            egui::Stroke::NONE,
+        ));
+    }
+}
+
+/// Positions of the time and value cursors, their distance, and the values of
+/// each line at the time cursors, which are mapped to the data by `data_x`.
+fn cursor_readout<
+    X: TimeseriesXAxis,
+    Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+>(
+    memory: &TimeseriesPlotMemory<X, Y>,
+    line_labels: &[(String, String, Option<String>)],
+    time: Option<[f64; 2]>,
+    value: Option<[f64; 2]>,
+    data_x: impl Fn(f64) -> Option<f64>,
+) -> String {
+    let mut readout = Vec::new();
+    if let Some([x1, x2]) = time {
+        let delta = x2 - x1;
+        readout.push(format!(
+            "X1: {}  X2: {}",
+            format_value(Some(x1), None),
+            format_value(Some(x2), None),
+        ));
+        readout.push(format!(
+            "ΔX: {}  1/ΔX: {}",
+            format_value(Some(delta), None),
+            format_value(Some(1.0 / delta), None),
+        ));
+        for (id, label, unit) in line_labels {
+            let value = |x| data_x(x).and_then(|x| memory.value_at(id, x));
+            readout.push(format!(
+                "{}: {} | {}",
+                label,
+                format_value(value(x1), unit.as_deref()),
+                format_value(value(x2), unit.as_deref()),
+            ));
+        }
+    }
+    if let Some([y1, y2]) = value {
+        // Lines sharing a plot usually share a unit, so we use the
+        // first one that is set.
+        let unit = line_labels.iter().find_map(|(_, _, unit)| unit.as_deref());
+        readout.push(format!(
+            "Y1: {}  Y2: {}",
+            format_value(Some(y1), unit),
+            format_value(Some(y2), unit),
+        ));
+        readout.push(format!("ΔY: {}", format_value(Some(y2 - y1), unit)));
+    }
+    readout.join("\n")
+}
+
+/// Table of the given measurements of each line over `range` on the X axis.
+fn measurement_table<
+    X: TimeseriesXAxis,
+    Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+>(
+    memory: &TimeseriesPlotMemory<X, Y>,
+    line_labels: &[(String, String, Option<String>)],
+    measurements: &[Measurement],
+    range: Option<(f64, f64)>,
+) -> String {
+    let mut rows = vec![std::iter::once(String::new())
+        .chain(measurements.iter().map(|m| m.label().to_string()))
+        .collect::<Vec<_>>()];
+    for (id, label, unit) in line_labels {
+        let mut row = vec![label.clone()];
+        for measurement in measurements {
+            let value =
+                range.and_then(|(start, end)| memory.measure(id, start..=end, *measurement));
+            row.push(match measurement {
+                Measurement::DutyCycle => format_value(value.map(|v| v * 100.0), Some("%")),
+                m if m.has_line_unit() => format_value(value, unit.as_deref()),
+                _ => format_value(value, None),
+            });
+        }
+        rows.push(row);
+    }
+    format_table(&rows)
 }
 
 impl<
@@ -158,8 +672,24 @@ impl<
         #[cfg(feature = "profiling")]
         puffin::profile_function!();
 
+        self.memory.update_math_lines();
+
+        // Start out with the requested duration, or whenever it is changed.
+        if let ViewMode::AttachedToEdge(duration) = self.view_mode {
+            if self.memory.follow_duration != Some(duration) {
+                self.memory.follow_duration = Some(duration);
+                self.memory.last_view_width = duration;
+                self.memory.reset_auto_bounds_next_frame = true;
+                if let Some(group) = &mut self.group {
+                    group.last_view_width = Some(duration);
+                }
+            }
+        }
+
         // Apply changes from other plots in the linked groupo
-        if let Some(group) = &self.group {
+        if let Some(group) = &mut self.group {
+            self.memory.sync_origin(group);
+
             if let Some(width) = group.last_view_width {
                 self.memory.last_view_width = width;
             }
@@ -170,14 +700,71 @@ impl<
                 .link_cursor(group.link_group_name.clone(), true, group.link_y);
         }
 
-        if let ViewMode::AttachedToEdge(_duration) = self.view_mode {
-            let end = self.memory.end().unwrap_or_default();
+        // With a trigger, the most recent trigger event is drawn at zero.
+        let (pre_trigger, post_trigger) = self
+            .trigger
+            .as_ref()
+            .map(|t| t.pre_post_trigger(self.memory.last_view_width))
+            .unwrap_or_default();
+        let trigger_point = self.trigger.as_ref().and_then(|trigger| {
+            let was_pinned = self.memory.display_point.is_some();
+            let trigger_point = self.memory.acquire(trigger, post_trigger);
+            if !was_pinned && trigger_point.is_some() {
+                self.memory.reset_auto_bounds_next_frame = true;
+            }
+            trigger_point
+        });
+        let x_offset = trigger_point.unwrap_or_default();
+        // Unlike Auto, Normal and Single mode don't free-run before the first
+        // trigger event, but wait for it with an empty view laid out around
+        // the trigger.
+        let waiting = self.trigger.is_some()
+            && trigger_point.is_none()
+            && self.memory.acquisition_mode() != AcquisitionMode::Auto;
+
+        if trigger_point.is_some() {
+            self.memory.update_accumulation(pre_trigger, post_trigger);
+        }
+        if self.trigger.is_some() {
+            self.memory.update_segments(pre_trigger, post_trigger);
+        }
+        if let Some(mask) = &self.mask {
+            self.memory.update_mask(mask);
+        }
+
+        let mut sweep_sections = Vec::new();
+
+        if trigger_point.is_some() || waiting {
+            self.plot = self.plot.include_x(-pre_trigger).include_x(post_trigger);
+        } else if let ViewMode::Sweep(window) = self.view_mode {
+            let end = self.memory.display_end().unwrap_or_default();
+            sweep_sections = sweep_sections_at(end, window);
+            self.plot = self.plot.include_x(0.0).include_x(window);
+        } else if let ViewMode::AttachedToEdge(_duration) = self.view_mode {
+            let end = self.memory.display_end().unwrap_or_default();
             self.plot = self
                 .plot
                 .include_x(end)
                 .include_x(end - self.memory.last_view_width);
         }
 
+        // Don't pan the plot while dragging a cursor.
+        let show_cursors = self.time_cursors || self.value_cursors;
+        if show_cursors && self.memory.cursors.grabbed() {
+            self.plot = self.plot.allow_drag(false);
+        }
+        let line_labels: Vec<(String, String, Option<String>)> = self
+            .lines
+            .iter()
+            .map(|l| {
+                (
+                    l.id.clone(),
+                    l.label.clone().unwrap_or_else(|| l.id.clone()),
+                    l.unit.clone(),
+                )
+            })
+            .collect();
+
         let plot_response = self
             .plot
             .legend(Legend::default().position(egui_plot::Corner::LeftTop))
@@ -189,23 +776,116 @@ impl<
 
                 self.memory.last_auto_bounds = plot_ui.auto_bounds().x;
 
-                for line in self.lines {
-                    // TODO: cropping
+                let width = plot_ui.transform().frame().width() * plot_ui.ctx().pixels_per_point();
+
+                let bounds = plot_ui.plot_bounds();
+                let (x_min, x_max) = (bounds.min()[0], bounds.max()[0]);
+                let visible = [(x_min + x_offset, x_max + x_offset, x_offset)];
+                let view = View {
+                    bounds,
+                    width,
+                    sections: match sweep_sections.as_slice() {
+                        [] => &visible,
+                        sections => sections,
+                    },
+                    crop: !sweep_sections.is_empty(),
+                };
+
+                let mut auto_color_index = 0;
+                let colors: Vec<Color32> = self
+                    .lines
+                    .iter()
+                    .map(|line| {
+                        line.color.unwrap_or_else(|| {
+                            auto_color_index += 1;
+                            auto_color(auto_color_index - 1)
+                        })
+                    })
+                    .collect();
+
+                // References are drawn dimmed and below the lines, in the
+                // color of the line they were captured from.
+                for (name, source) in self.memory.reference_sources() {
+                    let color = source
+                        .and_then(|id| self.lines.iter().position(|l| l.id == id))
+                        .map_or(plot_ui.ctx().style().visuals.text_color(), |i| colors[i]);
+                    let points = self.memory.plot_reference(&name, bounds, width);
+                    add_line(
+                        plot_ui,
+                        &points,
+                        color.gamma_multiply(0.4),
+                        Some(&name),
+                        None,
+                    );
+                }
 
-                    let points = PlotPoints::new(self.memory.plot(&line.id, plot_ui.plot_bounds()));
+                if trigger_point.is_some() && self.memory.overlay_segments {
+                    for index in 0..self.memory.segment_count() {
+                        for (line, color) in self.lines.iter().zip(&colors) {
+                            let points = self
+                                .memory
+                                .plot_segment(Some(index), &line.id, bounds, width)
+                                .unwrap_or_default();
+                            add_line(plot_ui, &points, color.gamma_multiply(0.15), None, None);
+                        }
+                    }
+                }
 
-                    let mut egui_line = egui_plot::Line::new(points);
-                    if let Some(label) = line.label {
-                        egui_line = egui_line.name(label);
+                for (line, color) in self.lines.iter().zip(colors) {
+                    if waiting {
+                        break;
                     }
-                    if let Some(color) = line.color {
-                        egui_line = egui_line.color(color);
+                    // While triggered, the selected segment or accumulated
+                    // traces are shown instead of the data.
+                    let accumulated = trigger_point.and_then(|_| {
+                        self.memory
+                            .plot_segment(None, &line.id, bounds, width)
+                            .or_else(|| self.memory.plot_accumulated(&line.id, bounds, width))
+                    });
+                    let (band, points) = match (accumulated, line.render_mode) {
+                        (Some(points), _) => (Vec::new(), points),
+                        (None, RenderMode::Line) => {
+                            (Vec::new(), line_points(self.memory, &line.id, &view))
+                        }
+                        (None, RenderMode::Band) => band_points(self.memory, &line.id, &view),
+                        (None, RenderMode::Density(colormap)) => {
+                            add_density(plot_ui, self.memory, line, color, &view, colormap);
+                            continue;
+                        }
+                    };
+
+                    if let Some(persistence) = &self.persistence {
+                        add_persistence(
+                            plot_ui,
+                            self.memory,
+                            line,
+                            color,
+                            bounds,
+                            persistence,
+                            &points,
+                        );
+                        if persistence.hide_lines {
+                            continue;
+                        }
                     }
-                    if let Some(width) = line.width {
-                        egui_line = egui_line.width(width);
+
+                    if !band.is_empty() {
+                        plot_ui.add(BandItem::new(band, color.gamma_multiply(0.4)));
                     }
+                    add_line(plot_ui, &points, color, line.label.as_deref(), line.width);
+                }
+
+                if let Some(mask) = &self.mask {
+                    add_mask(plot_ui, self.memory, mask, x_offset);
+                }
 
-                    plot_ui.line(egui_line);
+                if trigger_point.is_some() || waiting {
+                    let color = plot_ui.ctx().style().visuals.text_color();
+                    plot_ui.vline(
+                        egui_plot::VLine::new(0.0)
+                            .color(color.gamma_multiply(0.3))
+                            .style(egui_plot::LineStyle::dashed_loose()),
+                    );
                 }
 
                 //println!("{:?} {:?} {:?} {:?}",
@@ -215,6 +895,82 @@ impl<
                 //         self.memory.last_view_width);
             });
 
+        if trigger_point.is_some() || waiting {
+            paint_trigger_marker(ui, &plot_response.transform);
+        }
+
+        // Maps X axis values as displayed back to the data.
+        let data_x = |x: f64| match sweep_sections
+            .iter()
+            .find(|(x_min, x_max, offset)| (x_min - offset..=x_max - offset).contains(&x))
+        {
+            _ if waiting => None,
+            Some((_, _, offset)) => Some(x + offset),
+            None if sweep_sections.is_empty() => Some(x + x_offset),
+            None => None,
+        };
+
+        if show_cursors {
+            self.memory.cursors.show(
+                ui,
+                &plot_response.response,
+                &plot_response.transform,
+                self.time_cursors,
+                self.value_cursors,
+            );
+            self.memory.cursors.set_cursor_icon(ui);
+
+            let readout = cursor_readout(
+                self.memory,
+                &line_labels,
+                self.memory.cursors.time.filter(|_| self.time_cursors),
+                self.memory.cursors.value.filter(|_| self.value_cursors),
+                data_x,
+            );
+            paint_readout(
+                ui,
+                *plot_response.transform.frame(),
+                Align2::RIGHT_TOP,
+                readout,
+            );
+        }
+
+        if self.mask.is_some() {
+            let counts = self.memory.mask_counts();
+            paint_readout(
+                ui,
+                *plot_response.transform.frame(),
+                Align2::LEFT_BOTTOM,
+                format!("Mask: {} passed, {} failed", counts.passed, counts.failed),
+            );
+        }
+
+        if !self.measurements.is_empty() {
+            // Measure between the time cursors if shown, or the visible data
+            // otherwise.
+            let bounds = plot_response.transform.bounds();
+            let cursors = self.memory.cursors.time.filter(|_| self.time_cursors);
+            let range = match (cursors, sweep_sections.as_slice()) {
+                _ if waiting => None,
+                (Some([x1, x2]), _) => data_x(x1.min(x2)).zip(data_x(x1.max(x2))),
+                (None, []) => Some((bounds.min()[0] + x_offset, bounds.max()[0] + x_offset)),
+                (None, sections) => Some((
+                    sections.iter().map(|s| s.0).fold(f64::INFINITY, f64::min),
+                    sections
+                        .iter()
+                        .map(|s| s.1)
+                        .fold(f64::NEG_INFINITY, f64::max),
+                )),
+            };
+
+            paint_readout(
+                ui,
+                *plot_response.transform.frame(),
+                Align2::RIGHT_BOTTOM,
+                measurement_table(self.memory, &line_labels, &self.measurements, range),
+            );
+        }
+
         // For zooming, we have to reattach our plot to the edge afterwards
         if plot_response.response.hover_pos().is_some() && self.memory.last_auto_bounds {
             let zoom_delta = ui.input(|i| i.zoom_delta_2d());
@@ -230,3 +986,48 @@ impl<
         plot_response.response
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Where the given X axis range of a section is drawn.
+    fn drawn((x_min, x_max, offset): (f64, f64, f64)) -> (f64, f64) {
+        (x_min - offset, x_max - offset)
+    }
+
+    #[test]
+    fn sweep_leaves_a_gap_before_the_previous_sweep() {
+        let sections = sweep_sections_at(130.0, 100.0);
+        assert_eq!(sections, [(100.0, 130.0, 100.0), (32.0, 100.0, 0.0)]);
+        // The current sweep is drawn from the left, and the previous one
+        // continues after the gap up to the right edge.
+        assert_eq!(drawn(sections[0]), (0.0, 30.0));
+        assert_eq!(drawn(sections[1]), (32.0, 100.0));
+        assert_eq!(
+            drawn(sections[1]).0 - drawn(sections[0]).1,
+            100.0 * SWEEP_GAP_FRACTION
+        );
+    }
+
+    #[test]
+    fn sweep_wraps_at_the_window_width() {
+        // Right before the wrap, the gap has reached the right edge, so
+        // nothing of the previous sweep is left.
+        assert_eq!(sweep_sections_at(199.0, 100.0), [(100.0, 199.0, 100.0)]);
+        // At the wrap, the sweep that has just been completed becomes the
+        // previous one.
+        let sections = sweep_sections_at(200.0, 100.0);
+        assert_eq!(sections, [(200.0, 200.0, 200.0), (102.0, 200.0, 100.0)]);
+        assert_eq!(drawn(sections[1]), (2.0, 100.0));
+    }
+
+    #[test]
+    fn first_sweep_has_no_previous_data() {
+        // The section of the previous sweep lies before the start of the
+        // data, so only the current sweep is drawn.
+        let sections = sweep_sections_at(30.0, 100.0);
+        assert_eq!(sections[0], (0.0, 30.0, 0.0));
+        assert!(sections[1..].iter().all(|s| s.1 <= 0.0));
+    }
+}
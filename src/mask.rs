@@ -0,0 +1,146 @@
+/// What the X axis values of a [Mask] are relative to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaskReference {
+    /// Relative to the trigger event, tested on every trigger event.
+    #[default]
+    Trigger,
+    /// Absolute X axis values, tested whenever the data covering the mask
+    /// has been replaced, e.g. by a new capture with the same X axis values.
+    Absolute,
+}
+
+/// Upper and lower limits for a line, each given as a polygonal chain of
+/// points sorted by X. Between points, the limits are interpolated linearly.
+/// Outside of a chain, the respective limit doesn't apply.
+///
+/// Add it to a plot with [crate::TimeseriesPlot::mask] and check the results
+/// with [crate::TimeseriesPlotMemory::mask_counts].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mask {
+    pub(crate) line_id: String,
+    pub(crate) reference: MaskReference,
+    pub(crate) upper: Vec<(f64, f64)>,
+    pub(crate) lower: Vec<(f64, f64)>,
+}
+
+impl Mask {
+    /// Create an empty mask for the given line, relative to the trigger event.
+    pub fn new(line_id: impl ToString) -> Self {
+        Self {
+            line_id: line_id.to_string(),
+            reference: MaskReference::default(),
+            upper: Vec::new(),
+            lower: Vec::new(),
+        }
+    }
+
+    pub fn reference(mut self, reference: MaskReference) -> Self {
+        self.reference = reference;
+        self
+    }
+
+    /// Set the upper limit. Values above it are violations.
+    pub fn upper(mut self, points: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        self.upper = points.into_iter().collect();
+        self
+    }
+
+    /// Set the lower limit. Values below it are violations.
+    pub fn lower(mut self, points: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        self.lower = points.into_iter().collect();
+        self
+    }
+
+    /// The range on the X axis covered by any of the limits.
+    pub(crate) fn span(&self) -> Option<(f64, f64)> {
+        let xs = self.upper.iter().chain(&self.lower).map(|p| p.0);
+        let start = xs.clone().reduce(f64::min)?;
+        let end = xs.reduce(f64::max)?;
+        Some((start, end))
+    }
+
+    /// Whether the given point, relative to the reference, is outside of
+    /// the mask.
+    fn violated_by(&self, x: f64, y: f64) -> bool {
+        let limit = |chain: &[(f64, f64)]| {
+            let i = chain.partition_point(|p| p.0 < x);
+            let next = chain.get(i)?;
+            if next.0 == x {
+                return Some(next.1);
+            }
+            let previous = chain.get(i.checked_sub(1)?)?;
+            Some(previous.1 + (next.1 - previous.1) * (x - previous.0) / (next.0 - previous.0))
+        };
+        limit(&self.upper).is_some_and(|upper| y > upper)
+            || limit(&self.lower).is_some_and(|lower| y < lower)
+    }
+}
+
+/// Number of acquisitions that have passed or failed a mask test.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaskCounts {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct MaskState {
+    pub(crate) mask: Mask,
+    pub(crate) counts: MaskCounts,
+    // Reference point of the last tested acquisition.
+    pub(crate) tested_at: Option<f64>,
+    // Revision of the line data of the last tested acquisition.
+    pub(crate) tested_revision: Option<usize>,
+    // Points of the last tested acquisition outside of the mask.
+    pub(crate) violations: Vec<(f64, f64)>,
+}
+
+impl MaskState {
+    pub(crate) fn new(mask: Mask) -> Self {
+        Self {
+            mask,
+            counts: MaskCounts::default(),
+            tested_at: None,
+            tested_revision: None,
+            violations: Vec::new(),
+        }
+    }
+
+    /// Tests the given points against the mask placed at `reference`, and
+    /// counts the result. Gaps are skipped.
+    pub(crate) fn test<Y: Copy + Into<f64>>(
+        &mut self,
+        points: &[(f64, Y)],
+        reference: f64,
+        revision: usize,
+    ) {
+        let Some((start, end)) = self.mask.span() else {
+            return;
+        };
+
+        let i_begin = points.partition_point(|p| p.0 < reference + start);
+        let i_end = points.partition_point(|p| p.0 <= reference + end);
+        self.violations = points[i_begin..i_end.max(i_begin)]
+            .iter()
+            .map(|(x, y)| (*x, (*y).into()))
+            .filter(|(x, y)| !y.is_nan() && self.mask.violated_by(x - reference, *y))
+            .collect();
+
+        if self.violations.is_empty() {
+            self.counts.passed += 1;
+        } else {
+            self.counts.failed += 1;
+        }
+        self.tested_at = Some(reference);
+        self.tested_revision = Some(revision);
+    }
+
+    pub(crate) fn shift_x(&mut self, delta: f64) {
+        if self.mask.reference == MaskReference::Trigger {
+            self.tested_at = self.tested_at.map(|x| x + delta);
+        }
+        for violation in self.violations.iter_mut() {
+            violation.0 += delta;
+        }
+    }
+}
@@ -0,0 +1,117 @@
+/// Function of other lines, referenced by their id, that defines a math
+/// line. See [crate::TimeseriesPlotMemory::set_math_line].
+///
+/// Math lines are computed at the X axis values of their first source line.
+/// The second source line, if any, is interpolated linearly at those values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MathFunction {
+    /// `a - b`
+    Difference(String, String),
+    /// `a * b`
+    Product(String, String),
+    /// `a / b`
+    Quotient(String, String),
+    /// `line * factor + offset`
+    Scale {
+        line: String,
+        factor: f64,
+        offset: f64,
+    },
+    /// Change of the line per X axis unit.
+    Derivative(String),
+    /// Running integral of the line over the X axis, starting at zero.
+    Integral(String),
+}
+
+impl MathFunction {
+    pub fn difference(a: impl ToString, b: impl ToString) -> Self {
+        Self::Difference(a.to_string(), b.to_string())
+    }
+
+    pub fn product(a: impl ToString, b: impl ToString) -> Self {
+        Self::Product(a.to_string(), b.to_string())
+    }
+
+    pub fn quotient(a: impl ToString, b: impl ToString) -> Self {
+        Self::Quotient(a.to_string(), b.to_string())
+    }
+
+    pub fn scale(line: impl ToString, factor: f64, offset: f64) -> Self {
+        Self::Scale {
+            line: line.to_string(),
+            factor,
+            offset,
+        }
+    }
+
+    pub fn derivative(line: impl ToString) -> Self {
+        Self::Derivative(line.to_string())
+    }
+
+    pub fn integral(line: impl ToString) -> Self {
+        Self::Integral(line.to_string())
+    }
+
+    /// The ids of the lines this function depends on.
+    pub(crate) fn sources(&self) -> (&str, Option<&str>) {
+        match self {
+            Self::Difference(a, b) | Self::Product(a, b) | Self::Quotient(a, b) => (a, Some(b)),
+            Self::Scale { line, .. } | Self::Derivative(line) | Self::Integral(line) => {
+                (line, None)
+            }
+        }
+    }
+}
+
+/// Bookkeeping for a math line, whose cached points are kept alongside the
+/// other lines.
+#[derive(Debug)]
+pub(crate) struct MathLine {
+    pub(crate) function: MathFunction,
+    // Revisions of the source lines the cached points are based on.
+    pub(crate) source_revisions: Option<(usize, Option<usize>)>,
+    // Running integral up to the last computed point.
+    pub(crate) integral: f64,
+}
+
+impl MathLine {
+    pub(crate) fn new(function: MathFunction) -> Self {
+        Self {
+            function,
+            source_revisions: None,
+            integral: 0.0,
+        }
+    }
+
+    /// Computes the value for a point of the first source line, given the
+    /// point before it and the value of the second source line at the same
+    /// position, if any. Points must be passed in order. Returns None for gaps.
+    pub(crate) fn compute(
+        &mut self,
+        (x, y): (f64, f64),
+        previous: Option<(f64, f64)>,
+        b: Option<f64>,
+    ) -> Option<f64> {
+        if y.is_nan() {
+            return None;
+        }
+        let value = match &self.function {
+            MathFunction::Difference(..) => y - b?,
+            MathFunction::Product(..) => y * b?,
+            MathFunction::Quotient(..) => y / b?,
+            MathFunction::Scale { factor, offset, .. } => y * factor + offset,
+            MathFunction::Derivative(_) => {
+                let (x0, y0) = previous?;
+                (y - y0) / (x - x0)
+            }
+            MathFunction::Integral(_) => {
+                // Gaps don't contribute to the integral.
+                if let Some((x0, y0)) = previous.filter(|p| !p.1.is_nan()) {
+                    self.integral += (y + y0) / 2.0 * (x - x0);
+                }
+                self.integral
+            }
+        };
+        value.is_finite().then_some(value)
+    }
+}
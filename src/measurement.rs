@@ -0,0 +1,259 @@
+/// Automatic measurements on the raw data of a line, see
+/// [crate::TimeseriesPlotMemory::measure].
+///
+/// Edge based measurements use the 10% and 90% levels between the minimum
+/// and maximum as thresholds, so noise smaller than that is ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Measurement {
+    /// Difference between maximum and minimum.
+    PeakToPeak,
+    Min,
+    Max,
+    /// Mean of all samples.
+    Mean,
+    /// Root mean square of all samples.
+    Rms,
+    /// Number of periods per X axis unit, i.e. Hz for time based X axes.
+    Frequency,
+    /// Mean distance between consecutive rising edges.
+    Period,
+    /// Fraction of the time the signal is high, between 0 and 1. Measured
+    /// over full periods, between the crossings of half of the amplitude.
+    DutyCycle,
+    /// Mean time from 10% to 90% over all rising edges.
+    RiseTime,
+    /// Mean time from 90% to 10% over all falling edges.
+    FallTime,
+}
+
+impl Measurement {
+    /// All available measurements.
+    pub const ALL: [Measurement; 10] = [
+        Measurement::PeakToPeak,
+        Measurement::Min,
+        Measurement::Max,
+        Measurement::Mean,
+        Measurement::Rms,
+        Measurement::Frequency,
+        Measurement::Period,
+        Measurement::DutyCycle,
+        Measurement::RiseTime,
+        Measurement::FallTime,
+    ];
+
+    /// Short name, as shown in the measurement table.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Measurement::PeakToPeak => "Pk-Pk",
+            Measurement::Min => "Min",
+            Measurement::Max => "Max",
+            Measurement::Mean => "Mean",
+            Measurement::Rms => "RMS",
+            Measurement::Frequency => "Freq",
+            Measurement::Period => "Period",
+            Measurement::DutyCycle => "Duty",
+            Measurement::RiseTime => "Rise",
+            Measurement::FallTime => "Fall",
+        }
+    }
+
+    /// Whether the result is in the unit of the line, as opposed to the X
+    /// axis or no unit at all.
+    pub fn has_line_unit(&self) -> bool {
+        matches!(
+            self,
+            Measurement::PeakToPeak
+                | Measurement::Min
+                | Measurement::Max
+                | Measurement::Mean
+                | Measurement::Rms
+        )
+    }
+
+    /// Computes the measurement for the given points. Gaps, marked by NaN
+    /// values, are skipped.
+    pub(crate) fn compute<Y: Copy + Into<f64>>(&self, points: &[(f64, Y)]) -> Option<f64> {
+        let values = points
+            .iter()
+            .map(|p| p.1.into())
+            .filter(|y: &f64| !y.is_nan());
+        let (min, max) = values
+            .clone()
+            .fold(None, |acc: Option<(f64, f64)>, y| match acc {
+                Some((min, max)) => Some((min.min(y), max.max(y))),
+                None => Some((y, y)),
+            })?;
+        let level = |fraction: f64| min + (max - min) * fraction;
+
+        match self {
+            Measurement::PeakToPeak => Some(max - min),
+            Measurement::Min => Some(min),
+            Measurement::Max => Some(max),
+            Measurement::Mean => {
+                let (sum, count) = values.fold((0.0, 0), |(s, c), y| (s + y, c + 1));
+                Some(sum / count as f64)
+            }
+            Measurement::Rms => {
+                let (sum, count) = values.fold((0.0, 0), |(s, c), y| (s + y * y, c + 1));
+                Some((sum / count as f64).sqrt())
+            }
+            Measurement::Frequency => Measurement::Period.compute(points).map(|p| 1.0 / p),
+            Measurement::Period => {
+                let rising: Vec<f64> = edges(points, level(0.1), level(0.9))
+                    .filter(|e| e.rising)
+                    .map(|e| e.middle)
+                    .collect();
+                (rising.len() >= 2)
+                    .then(|| (rising[rising.len() - 1] - rising[0]) / (rising.len() - 1) as f64)
+            }
+            Measurement::DutyCycle => {
+                // Sum up the high time of every period that starts and ends
+                // with a rising edge.
+                let (mut first, mut last, mut rising_at) = (None, None, None);
+                let (mut high_time, mut full_periods_high_time) = (0.0, 0.0);
+                for edge in edges(points, level(0.1), level(0.9)) {
+                    let x = edge.middle;
+                    if edge.rising {
+                        first.get_or_insert(x);
+                        last = Some(x);
+                        rising_at = Some(x);
+                        full_periods_high_time = high_time;
+                    } else if let Some(rising_at) = rising_at.take() {
+                        high_time += x - rising_at;
+                    }
+                }
+                match (first, last) {
+                    (Some(first), Some(last)) if last > first => {
+                        Some(full_periods_high_time / (last - first))
+                    }
+                    _ => None,
+                }
+            }
+            Measurement::RiseTime | Measurement::FallTime => {
+                let rising = *self == Measurement::RiseTime;
+                let (sum, count) = edges(points, level(0.1), level(0.9))
+                    .filter(|e| e.rising == rising)
+                    .fold((0.0, 0), |(s, c), e| (s + e.end - e.start, c + 1));
+                (count > 0).then(|| sum / count as f64)
+            }
+        }
+    }
+}
+
+// A transition between the low and the high threshold.
+struct Edge {
+    rising: bool,
+    // Where the signal last left the threshold it started from.
+    start: f64,
+    // Where the signal last crossed the level halfway between the thresholds.
+    middle: f64,
+    // Where the signal reached the other threshold.
+    end: f64,
+}
+
+// Finds all transitions between `low` and `high`, with crossings linearly
+// interpolated between points. Gaps reset the state, so edges never span them.
+fn edges<Y: Copy + Into<f64>>(
+    points: &[(f64, Y)],
+    low: f64,
+    high: f64,
+) -> impl Iterator<Item = Edge> + '_ {
+    let crossing =
+        |x0: f64, y0: f64, x1: f64, y1: f64, level: f64| x0 + (x1 - x0) * (level - y0) / (y1 - y0);
+
+    let middle = (low + high) / 2.0;
+    let mut is_high: Option<bool> = None;
+    let (mut left_at, mut middle_at) = (f64::NAN, f64::NAN);
+    points.windows(2).filter_map(move |w| {
+        let ((x0, y0), (x1, y1)) = ((w[0].0, w[0].1.into()), (w[1].0, w[1].1.into()));
+        if y0.is_nan() || y1.is_nan() {
+            is_high = None;
+            return None;
+        }
+        if is_high.is_none() {
+            is_high = if y0 <= low {
+                Some(false)
+            } else if y0 >= high {
+                Some(true)
+            } else {
+                None
+            };
+        }
+
+        let mut edge = None;
+        match is_high {
+            Some(false) if y0 <= low && y1 > low => {
+                left_at = crossing(x0, y0, x1, y1, low);
+            }
+            Some(true) if y0 >= high && y1 < high => {
+                left_at = crossing(x0, y0, x1, y1, high);
+            }
+            _ => {}
+        }
+        match is_high {
+            Some(false) if y0 < middle && y1 >= middle => {
+                middle_at = crossing(x0, y0, x1, y1, middle);
+            }
+            Some(true) if y0 > middle && y1 <= middle => {
+                middle_at = crossing(x0, y0, x1, y1, middle);
+            }
+            _ => {}
+        }
+        match is_high {
+            Some(false) if y0 < high && y1 >= high => {
+                let end = crossing(x0, y0, x1, y1, high);
+                edge = Some(Edge {
+                    rising: true,
+                    start: left_at,
+                    middle: middle_at,
+                    end,
+                });
+                is_high = Some(true);
+            }
+            Some(true) if y0 > low && y1 <= low => {
+                let end = crossing(x0, y0, x1, y1, low);
+                edge = Some(Edge {
+                    rising: false,
+                    start: left_at,
+                    middle: middle_at,
+                    end,
+                });
+                is_high = Some(false);
+            }
+            None if y1 <= low => is_high = Some(false),
+            None if y1 >= high => is_high = Some(true),
+            _ => {}
+        }
+        edge
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duty_cycle_with_asymmetric_edges() {
+        // A quadratic rise over the first 0.4 of every period, and an
+        // immediate fall at 0.7. Half of the amplitude is crossed at
+        // 0.4 * sqrt(0.5) on the way up.
+        let points: Vec<(f64, f64)> = (0..50_000)
+            .map(|i| {
+                let x = i as f64 * 1e-4;
+                let t = x.fract();
+                let y = match t {
+                    t if t < 0.4 => (t / 0.4).powi(2),
+                    t if t < 0.7 => 1.0,
+                    _ => 0.0,
+                };
+                (x, y)
+            })
+            .collect();
+
+        let expected = 0.7 - 0.4 * 0.5f64.sqrt();
+        let duty_cycle = Measurement::DutyCycle.compute(&points).unwrap();
+        assert!((duty_cycle - expected).abs() < 1e-3, "{duty_cycle}");
+        let period = Measurement::Period.compute(&points).unwrap();
+        assert!((period - 1.0).abs() < 1e-3, "{period}");
+    }
+}
@@ -1,17 +1,39 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::iter::Peekable;
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
 
 use egui_plot::PlotBounds;
 
+use crate::accumulate::*;
+use crate::colormap::Colormap;
+use crate::cursors::Cursors;
+use crate::density::*;
+use crate::mask::*;
+use crate::math::*;
+use crate::measurement::*;
+use crate::persistence::*;
+use crate::reference::*;
 use crate::traits::*;
+use crate::trigger::*;
 
-// min/max downsampling produces two values per bucket, so this means
-// a downsampling factor of 4.
+// Number of points from the previous cache level that are aggregated into a
+// single bucket. Depending on the downsampling method, a bucket produces one
+// or more points, so this is not necessarily the downsampling factor.
 const DOWNSAMPLING_BUCKET_SIZE: usize = 8;
 
-const MAX_POINTS: usize = 4000; // TODO: use screen size
+// Further downsampled levels are only built while the previous level contains
+// more points than this.
+const MIN_POINTS_PER_LEVEL: usize = 1000;
+
+// Two points per pixel, i.e. one min/max pair per pixel column.
+const DEFAULT_POINTS_PER_PIXEL: f32 = 2.0;
 
 const MAX_DOWNSAMPLING_STEPS: usize = 5;
 
+// Number of samples used to estimate the median interval for gap detection.
+const MEDIAN_INTERVAL_SAMPLES: usize = 100;
+
 #[derive(Debug)]
 struct CacheDescriptor<X, Y> {
     len: usize,
@@ -19,53 +41,326 @@ struct CacheDescriptor<X, Y> {
     //last_data_point: Option<(X, Option<Y>)>,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Aggregation used to build the downsampled cache levels of a line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum DownsamplingMethod {
+    /// Don't downsample at all, every raw sample is kept and plotted.
     None,
+    /// Keep the minimum and maximum of each bucket. Preserves outliers and
+    /// glitches, but draws noisy signals as a zig-zag.
+    #[default]
     MinMax,
+    /// Replace each bucket with its average. Gives a smooth trace for noisy
+    /// signals, at the cost of hiding short spikes.
     Mean,
+    /// Largest-Triangle-Three-Buckets: keep the one point per bucket that
+    /// forms the largest triangle with the point kept for the previous bucket
+    /// and the average of the next bucket. Visually close to the raw data
+    /// with half as many points as [DownsamplingMethod::MinMax].
+    Lttb,
+    /// Keep the first, minimum, maximum and last point of each bucket. Unlike
+    /// [DownsamplingMethod::MinMax], the transitions between buckets are
+    /// preserved as well. When plotted, the data is reduced per pixel column,
+    /// so the downsampled line is rasterized exactly like the raw data.
+    M4,
 }
 
 impl DownsamplingMethod {
+    /// Number of points a full bucket without gaps is reduced to.
+    fn points_per_bucket(&self) -> usize {
+        match self {
+            Self::None => DOWNSAMPLING_BUCKET_SIZE,
+            Self::MinMax => 2,
+            Self::Mean => 1,
+            Self::Lttb => 1,
+            Self::M4 => 4,
+        }
+    }
+
+    /// Number of buckets the visible data is reduced to for a plot that is
+    /// `width` pixels wide. The same for every method, with `points_per_pixel`
+    /// counted in [DownsamplingMethod::MinMax] points, so methods with fewer
+    /// points per bucket actually draw fewer points.
+    fn buckets_for_width(&self, width: f32, points_per_pixel: f32) -> usize {
+        let per_bucket = Self::MinMax.points_per_bucket() as f32;
+        let buckets = (width * points_per_pixel / per_bucket).ceil() as usize;
+        match self {
+            // M4 is only exact if there is at least one bucket per pixel.
+            Self::M4 => usize::max(buckets, width.ceil() as usize),
+            _ => usize::max(buckets, 1),
+        }
+    }
+
+    /// Downsamples `points` into buckets of equal width on the X axis, with
+    /// bucket boundaries at `x_min + k * bucket_width`.
+    fn downsample_by_x<Y: Default + num_traits::Float + num_traits::float::TotalOrder>(
+        &self,
+        points: &[(f64, Y)],
+        x_min: f64,
+        bucket_width: f64,
+        out: &mut Vec<(f64, Y)>,
+    ) {
+        let bucket_of = |x: f64| ((x - x_min) / bucket_width).floor() as i64;
+
+        let mut starts: Vec<usize> = (0..points.len())
+            .filter(|&i| i == 0 || bucket_of(points[i].0) != bucket_of(points[i - 1].0))
+            .collect();
+        starts.push(points.len());
+
+        for (i, bounds) in starts.windows(2).enumerate() {
+            let next_end = starts.get(i + 2).copied().unwrap_or(points.len());
+            self.downsample(
+                &points[bounds[0]..bounds[1]],
+                &points[bounds[1]..next_end],
+                out,
+            );
+        }
+    }
+
+    /// Number of buckets following a bucket that are taken into account when
+    /// downsampling it. These have to be recomputed when new data arrives.
+    fn lookahead_buckets(&self) -> usize {
+        match self {
+            Self::Lttb => 1,
+            _ => 0,
+        }
+    }
+
+    /// Downsamples `bucket` and appends the result to `out`. `next` is the
+    /// following bucket, which may be empty or incomplete, and `out` contains
+    /// the results of all preceding buckets.
+    ///
+    /// Gaps are kept at every level as a single point each, and the runs of
+    /// data before and after a gap are downsampled separately, so a bucket
+    /// containing a gap is reduced to more points than others.
     fn downsample<Y: Default + num_traits::Float + num_traits::float::TotalOrder>(
         &self,
         bucket: &[(f64, Y)],
-    ) -> [(f64, Y); 2] {
-        match self {
-            Self::None => {
-                [(0.0, Y::default()), (0.0, Y::default())] // TODO
+        next: &[(f64, Y)],
+        out: &mut Vec<(f64, Y)>,
+    ) {
+        if *self == Self::None {
+            out.extend_from_slice(bucket);
+            return;
+        }
+
+        let mut rest = bucket;
+        while let Some(gap) = rest.iter().position(|p| p.1.is_nan()) {
+            if gap > 0 {
+                // The run ends at the gap, which is what comes next.
+                self.downsample_run(&rest[..gap], &rest[gap..], out);
+            }
+            // Consecutive gaps are collapsed into one.
+            if gap > 0 || rest.len() == bucket.len() {
+                out.push(rest[gap]);
             }
+            rest = &rest[gap + 1..];
+        }
+        if !rest.is_empty() {
+            self.downsample_run(rest, next, out);
+        }
+    }
+
+    /// Downsamples a run of points without gaps, see [Self::downsample].
+    fn downsample_run<Y: Default + num_traits::Float + num_traits::float::TotalOrder>(
+        &self,
+        bucket: &[(f64, Y)],
+        next: &[(f64, Y)],
+        out: &mut Vec<(f64, Y)>,
+    ) {
+        match self {
+            Self::None => out.extend_from_slice(bucket),
             Self::MinMax => {
-                let (min_i, min) = bucket
-                    .iter()
-                    .enumerate()
-                    .min_by(|(_, x), (_, y)| x.1.total_cmp(&y.1))
-                    .unwrap();
-                let (max_i, max) = bucket
-                    .iter()
-                    .enumerate()
-                    .max_by(|(_, x), (_, y)| x.1.total_cmp(&y.1))
-                    .unwrap();
+                let (min_i, max_i) = Self::min_max_indices(bucket);
                 match (min_i, max_i) {
-                    (i, j) if i < j => [*min, *max],
-                    _ => [*max, *min],
+                    (i, j) if i < j => out.extend([bucket[i], bucket[j]]),
+                    (i, j) => out.extend([bucket[j], bucket[i]]),
+                }
+            }
+            Self::M4 => {
+                let (min_i, max_i) = Self::min_max_indices(bucket);
+                let (first, last) = (bucket[0], bucket[bucket.len() - 1]);
+                match (min_i, max_i) {
+                    (i, j) if i < j => out.extend([first, bucket[i], bucket[j], last]),
+                    (i, j) => out.extend([first, bucket[j], bucket[i], last]),
                 }
             }
             Self::Mean => {
-                [(0.0, Y::default()), (0.0, Y::default())] // TODO
+                let (sum_x, sum_y) = bucket
+                    .iter()
+                    .fold((0.0, Y::zero()), |(sx, sy), (x, y)| (sx + x, sy + *y));
+                let n = bucket.len();
+                out.push((sum_x / n as f64, sum_y / Y::from(n).unwrap()));
+            }
+            Self::Lttb => {
+                // The very first and the (current) last point of the data, as
+                // well as the points next to gaps, are always kept, so the
+                // line and its segments have the correct extent.
+                let anchor = out.last().filter(|p| !p.1.is_nan());
+                let is_end = next.is_empty() || next.iter().any(|p| p.1.is_nan());
+                let (Some((a_x, a_y)), false) = (anchor, is_end) else {
+                    let point = if anchor.is_none() {
+                        bucket[0]
+                    } else {
+                        bucket[bucket.len() - 1]
+                    };
+                    out.push(point);
+                    return;
+                };
+                let a_y = a_y.to_f64().unwrap();
+
+                let (sum_x, sum_y) = next.iter().fold((0.0, 0.0), |(sx, sy), (x, y)| {
+                    (sx + x, sy + y.to_f64().unwrap())
+                });
+                let (c_x, c_y) = (sum_x / next.len() as f64, sum_y / next.len() as f64);
+
+                let area = |(b_x, b_y): &(f64, Y)| {
+                    let b_y = b_y.to_f64().unwrap();
+                    ((a_x - c_x) * (b_y - a_y) - (a_x - b_x) * (c_y - a_y)).abs()
+                };
+                let point = bucket
+                    .iter()
+                    .max_by(|p, q| area(p).total_cmp(&area(q)))
+                    .unwrap();
+                out.push(*point);
             }
         }
     }
+
+    fn min_max_indices<Y: num_traits::float::TotalOrder>(bucket: &[(f64, Y)]) -> (usize, usize) {
+        let (min_i, _) = bucket
+            .iter()
+            .enumerate()
+            .min_by(|(_, x), (_, y)| x.1.total_cmp(&y.1))
+            .unwrap();
+        let (max_i, _) = bucket
+            .iter()
+            .enumerate()
+            .max_by(|(_, x), (_, y)| x.1.total_cmp(&y.1))
+            .unwrap();
+        (min_i, max_i)
+    }
+}
+
+/// Detection of gaps in a line based on the spacing of its samples, for data
+/// that has no explicit markers for missing values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GapThreshold {
+    /// Only missing values interrupt the line.
+    #[default]
+    None,
+    /// Interrupt the line wherever two consecutive samples are further apart
+    /// than this, in plot units (i.e. seconds for [std::time::Instant]).
+    Fixed(f64),
+    /// Interrupt the line wherever two consecutive samples are further apart
+    /// than this multiple of the median sample interval. The median is
+    /// estimated from the first samples of the line.
+    MedianInterval(f64),
+}
+
+// Plot bounds, width, points per pixel, downsampling method, and revision,
+// start and end of the raw data.
+type BandKey = (
+    PlotBounds,
+    f32,
+    f32,
+    DownsamplingMethod,
+    usize,
+    Option<f64>,
+    Option<f64>,
+);
+
+/// A single level of the downsampling pyramid.
+#[derive(Debug, Default)]
+struct CacheLevel<Y> {
+    points: Vec<(f64, Y)>,
+    /// Index into `points` just past the points of each bucket. Buckets
+    /// containing gaps are reduced to more points than others, see
+    /// [DownsamplingMethod::downsample]. Empty for the first level.
+    bucket_ends: Vec<usize>,
+    /// Number of points of the previous level that belonged to the first
+    /// bucket of this level, but have since been evicted from the front.
+    evicted: usize,
+}
+
+impl<Y: Default + num_traits::Float + num_traits::float::TotalOrder> CacheLevel<Y> {
+    /// Range of points in the previous level that make up the given bucket.
+    fn bucket_range(&self, bucket: usize, source_len: usize) -> Range<usize> {
+        let start = (bucket * DOWNSAMPLING_BUCKET_SIZE).saturating_sub(self.evicted);
+        let end = ((bucket + 1) * DOWNSAMPLING_BUCKET_SIZE).saturating_sub(self.evicted);
+        usize::min(start, source_len)..usize::min(end, source_len)
+    }
+
+    /// Range of points in this level that the given buckets were reduced to.
+    fn points_of(&self, buckets: Range<usize>) -> Range<usize> {
+        let end_of = |bucket: usize| match bucket.min(self.bucket_ends.len()) {
+            0 => 0,
+            bucket => self.bucket_ends[bucket - 1],
+        };
+        end_of(buckets.start)..end_of(buckets.end)
+    }
+
+    /// Bucket the given point of this level belongs to.
+    fn bucket_of(&self, point: usize) -> usize {
+        self.bucket_ends.partition_point(|end| *end <= point)
+    }
+
+    /// Downsamples the next bucket and appends it to this level.
+    fn push_bucket(&mut self, method: DownsamplingMethod, bucket: &[(f64, Y)], next: &[(f64, Y)]) {
+        method.downsample(bucket, next, &mut self.points);
+        self.bucket_ends.push(self.points.len());
+    }
+
+    /// Removes all buckets from `buckets` on.
+    fn truncate_buckets(&mut self, buckets: usize) {
+        let end = self.points_of(0..buckets).end;
+        self.points.truncate(end);
+        self.bucket_ends.truncate(buckets);
+    }
+
+    /// Removes the first `buckets` buckets, returning the number of points
+    /// removed.
+    fn drain_buckets(&mut self, buckets: usize) -> usize {
+        let buckets = usize::min(buckets, self.bucket_ends.len());
+        let end = self.points_of(0..buckets).end;
+        self.points.drain(..end);
+        self.bucket_ends.drain(..buckets);
+        for bucket_end in &mut self.bucket_ends {
+            *bucket_end -= end;
+        }
+        end
+    }
+
+    /// Replaces the first buckets by the ones in `front`.
+    fn replace_front(&mut self, front: CacheLevel<Y>) {
+        let replaced = usize::min(front.bucket_ends.len(), self.bucket_ends.len());
+        let old_end = self.points_of(0..replaced).end;
+        let new_end = front.points.len();
+        self.points.splice(..old_end, front.points);
+        self.bucket_ends
+            .splice(..replaced, front.bucket_ends.iter().copied());
+        for end in &mut self.bucket_ends[front.bucket_ends.len()..] {
+            *end = *end - old_end + new_end;
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct TimeseriesLineMemory<X, Y> {
     downsampling_method: DownsamplingMethod,
     cached_data: Option<CacheDescriptor<X, Y>>,
-    cache_levels: Vec<Vec<(f64, Y)>>,
-    view_cache: Option<(PlotBounds, Vec<[f64; 2]>)>,
-    // TODO: Fix x axis behaviour
-    x_axis_origin: Option<X>,
+    cache_levels: Vec<CacheLevel<Y>>,
+    view_cache: Option<(PlotBounds, f32, Vec<[f64; 2]>)>,
+    // Plot bounds, width and data the band was computed for, see
+    // [Self::plot_band].
+    band_cache: Option<(BandKey, Vec<[f64; 4]>)>,
+    // X axis value of the last data point, including points without a value.
+    last_x: Option<f64>,
+    gap_threshold: GapThreshold,
+    median_interval: Option<f64>,
+    // Incremented whenever the caches are cleared, so derived lines know
+    // when to start over.
+    revision: usize,
 }
 
 impl<
@@ -79,43 +374,51 @@ impl<
             cached_data: None,
             cache_levels: Vec::new(),
             view_cache: None,
-            x_axis_origin: None,
+            band_cache: None,
+            last_x: None,
+            gap_threshold: GapThreshold::default(),
+            median_interval: None,
+            revision: 0,
         }
     }
 
     pub fn clear_caches(&mut self) {
         self.cache_levels.truncate(1);
-        if self.cache_levels.len() == 0 {
-            self.cache_levels.push(Vec::new());
+        if self.cache_levels.is_empty() {
+            self.cache_levels.push(CacheLevel::default());
         } else {
-            self.cache_levels[0].truncate(0);
+            self.cache_levels[0].points.truncate(0);
         }
         // Also clear the cache descriptor so update_cache will rebuild from scratch
         self.cached_data = None;
         self.view_cache = None;
+        self.last_x = None;
+        self.median_interval = None;
+        self.revision += 1;
     }
 
-    fn rebuild_caches<
-        'a,
-        I: Iterator<Item = (X, Option<Y>)> + ExactSizeIterator + DoubleEndedIterator + 'a,
-    >(
+    fn rebuild_caches<'a, I: Iterator<Item = (X, Option<Y>)> + 'a>(
         &mut self,
         data: I,
+        origin: &mut Option<X>,
     ) {
         self.clear_caches();
-        self.extend_caches(data);
+        self.extend_caches(data, origin);
     }
 
-    fn extend_caches<
-        'a,
-        I: Iterator<Item = (X, Option<Y>)> + ExactSizeIterator + DoubleEndedIterator + 'a,
-    >(
+    fn extend_caches<'a, I: Iterator<Item = (X, Option<Y>)> + 'a>(
         &mut self,
         data: I,
+        origin: &mut Option<X>,
     ) {
+        self.extend_points(data.map(|(t, y)| (t.to_f64(origin), y)));
+    }
+
+    /// Appends points that are already converted to plot coordinates.
+    fn extend_points<I: Iterator<Item = (f64, Option<Y>)>>(&mut self, data: I) {
         //println!("extending caches");
-        if self.cache_levels.len() == 0 {
-            self.cache_levels.push(Vec::new());
+        if self.cache_levels.is_empty() {
+            self.cache_levels.push(CacheLevel::default());
         }
 
         //let len = data.len();
@@ -130,31 +433,243 @@ impl<
         //    self.cache_levels[0].push([x, y]);
         //}
 
-        let new = data.filter_map(|(t, y)| y.map(|y| (t.to_f64(&mut self.x_axis_origin), y)));
-        self.cache_levels[0].extend(new);
+        // Missing values are stored as NaN, marking a gap in the line.
+        // Consecutive missing values are collapsed into a single gap.
+        let threshold = self.gap_threshold_x();
+        let level_0 = &mut self.cache_levels[0].points;
+        let mut in_gap = level_0.last().is_some_and(|p| p.1.is_nan());
+        for (x, y) in data {
+            // Samples that are too far apart are separated by a gap as well.
+            if let (Some(threshold), Some(last_x)) = (threshold, self.last_x) {
+                if !in_gap && x - last_x > threshold {
+                    level_0.push(((last_x + x) / 2.0, Y::nan()));
+                    in_gap = true;
+                }
+            }
+            self.last_x = Some(x);
+
+            match y {
+                Some(y) => {
+                    in_gap = y.is_nan();
+                    level_0.push((x, y));
+                }
+                None if !in_gap => {
+                    in_gap = true;
+                    level_0.push((x, Y::nan()));
+                }
+                None => {}
+            }
+        }
         //println!("first-layer update: {:?} ({:?}/{:?})", t.elapsed(), len - skip, len);
         //println!("first-layer update: {:?} ({:?})", t.elapsed(), len);
 
+        if threshold.is_none() {
+            self.estimate_median_interval();
+        }
+
+        self.extend_downsampled_caches();
+    }
+
+    /// Maximum distance between two samples on the X axis before the line is
+    /// interrupted, if known.
+    fn gap_threshold_x(&self) -> Option<f64> {
+        match self.gap_threshold {
+            GapThreshold::None => None,
+            GapThreshold::Fixed(threshold) => Some(threshold),
+            GapThreshold::MedianInterval(factor) => self.median_interval.map(|m| m * factor),
+        }
+    }
+
+    /// For [GapThreshold::MedianInterval], estimates the sample interval once
+    /// enough samples are known, and inserts gaps into the samples seen so far.
+    fn estimate_median_interval(&mut self) {
+        let level_0 = &self.cache_levels[0].points;
+        if !matches!(self.gap_threshold, GapThreshold::MedianInterval(_))
+            || level_0.len() < MEDIAN_INTERVAL_SAMPLES
+        {
+            return;
+        }
+
+        let mut intervals: Vec<f64> = level_0
+            .windows(2)
+            .filter(|w| !w[0].1.is_nan() && !w[1].1.is_nan())
+            .map(|w| w[1].0 - w[0].0)
+            .collect();
+        if intervals.is_empty() {
+            return;
+        }
+        let median = intervals.len() / 2;
+        let (_, median, _) = intervals.select_nth_unstable_by(median, f64::total_cmp);
+        self.median_interval = Some(*median);
+
+        let threshold = self.gap_threshold_x().unwrap();
+        let mut with_gaps = Vec::with_capacity(level_0.len());
+        for (i, point) in level_0.iter().enumerate() {
+            if let Some(previous) = i.checked_sub(1).map(|i| level_0[i]) {
+                if !previous.1.is_nan() && !point.1.is_nan() && point.0 - previous.0 > threshold {
+                    with_gaps.push(((previous.0 + point.0) / 2.0, Y::nan()));
+                }
+            }
+            with_gaps.push(*point);
+        }
+        self.cache_levels.truncate(1);
+        self.cache_levels[0].points = with_gaps;
+    }
+
+    /// Brings the downsampled cache levels up to date with the first level.
+    fn extend_downsampled_caches(&mut self) {
+        // Without downsampling, only the raw data is ever plotted, so there is
+        // no point in building any further levels.
+        if self.downsampling_method == DownsamplingMethod::None || self.cache_levels.is_empty() {
+            return;
+        }
+
+        let method = self.downsampling_method;
+        let lookahead = method.lookahead_buckets();
+        // First point of the previous level that has been recomputed. Since
+        // buckets with gaps yield more points, this may lie before its last
+        // bucket.
+        let mut changed_from = None;
         for i in 1..=MAX_DOWNSAMPLING_STEPS {
             if i >= self.cache_levels.len() {
-                if self.cache_levels.last().unwrap().len() > MAX_POINTS {
-                    self.cache_levels.push(Vec::new());
+                if self.cache_levels.last().unwrap().points.len() > MIN_POINTS_PER_LEVEL {
+                    self.cache_levels.push(CacheLevel::default());
                 } else {
                     break;
                 }
             }
 
-            let len = usize::max(self.cache_levels[i].len(), 2) - 2;
-            self.cache_levels[i].truncate(len);
+            let (previous, current) = self.cache_levels.split_at_mut(i);
+            let (source, level) = (&previous[i - 1].points, &mut current[0]);
 
-            let skip = self.cache_levels[i].len() * (DOWNSAMPLING_BUCKET_SIZE / 2);
-            let new: Vec<(f64, Y)> = (&self.cache_levels[i - 1][skip..])
-                .chunks(DOWNSAMPLING_BUCKET_SIZE)
-                .map(|c| self.downsampling_method.downsample(c))
-                .flatten()
-                .collect();
-            //println!("{:?}", new.len());
-            self.cache_levels[i].extend(new);
+            // The last bucket may have been incomplete, so we recompute it,
+            // along with any buckets covering points of the previous level
+            // that have changed, and the buckets that depended on them.
+            let first_bucket = match changed_from {
+                Some(point) => point / DOWNSAMPLING_BUCKET_SIZE,
+                None => level.bucket_ends.len().saturating_sub(1),
+            };
+            let first_bucket = first_bucket
+                .saturating_sub(lookahead)
+                .min(level.bucket_ends.len());
+            level.truncate_buckets(first_bucket);
+            changed_from = Some(level.points.len());
+
+            for bucket in first_bucket.. {
+                let range = level.bucket_range(bucket, source.len());
+                if range.is_empty() {
+                    break;
+                }
+                let next = level.bucket_range(bucket + 1, source.len());
+                level.push_bucket(method, &source[range], &source[next]);
+            }
+        }
+    }
+
+    /// Removes the first `count` points from the first cache level and trims
+    /// the downsampled levels accordingly, without rebuilding them.
+    fn evict_front(&mut self, count: usize) {
+        self.cache_levels[0].points.drain(..count);
+
+        let method = self.downsampling_method;
+        let recompute = 1 + method.lookahead_buckets();
+        let mut evicted = count;
+        for i in 1..self.cache_levels.len() {
+            let (previous, current) = self.cache_levels.split_at_mut(i);
+            let (source, level) = (&previous[i - 1].points, &mut current[0]);
+
+            // Drop all buckets whose points have been evicted completely.
+            level.evicted += evicted;
+            let buckets = level.evicted / DOWNSAMPLING_BUCKET_SIZE;
+            level.evicted %= DOWNSAMPLING_BUCKET_SIZE;
+            evicted = level.drain_buckets(buckets);
+
+            // The first bucket is now incomplete, and its input from the
+            // previous level may have changed as well, so we recompute it.
+            // For LTTB, this is not exactly what a full rebuild would produce,
+            // as every bucket depends on the one before it.
+            let mut front = CacheLevel::default();
+            for bucket in 0..recompute {
+                let range = level.bucket_range(bucket, source.len());
+                if range.is_empty() {
+                    break;
+                }
+                let next = level.bucket_range(bucket + 1, source.len());
+                front.push_bucket(method, &source[range], &source[next]);
+            }
+            level.replace_front(front);
+        }
+    }
+
+    /// Handles data that has been removed from the front and possibly
+    /// appended to at the back, like a ring buffer, by updating the caches
+    /// in place. If the data doesn't line up with the cached data, the caches
+    /// are rebuilt instead.
+    fn slide_caches<I: Iterator<Item = (X, Option<Y>)> + DoubleEndedIterator>(
+        &mut self,
+        mut data: Peekable<I>,
+        old_len: usize,
+        new_len: usize,
+        origin: &mut Option<X>,
+    ) {
+        let (Some(last_x), Some((first, first_y))) = (self.last_x, data.peek().cloned()) else {
+            self.rebuild_caches(data, origin);
+            return;
+        };
+        let first_x = first.to_f64(origin);
+
+        // Collect the appended points from the back, up to and including the
+        // last point we already know.
+        let mut appended = Vec::new();
+        let mut found_last = false;
+        while let Some((x, y)) = data.next_back() {
+            let x_f64 = x.clone().to_f64(origin);
+            appended.push((x, y));
+            if x_f64 <= last_x {
+                found_last = x_f64 == last_x;
+                break;
+            }
+        }
+
+        let num_appended = appended.len() - 1;
+        let num_kept = new_len - num_appended;
+        let level_0 = &self.cache_levels[0].points;
+        let evict = level_0.partition_point(|p| p.0 < first_x);
+        let lines_up = found_last
+            && num_kept <= old_len
+            && evict <= old_len - num_kept
+            && first_y.is_none_or(|y| level_0.get(evict) == Some(&(first_x, y)));
+
+        appended.reverse();
+        if !lines_up {
+            self.rebuild_caches(data.chain(appended), origin);
+            return;
+        }
+
+        self.evict_front(evict);
+        self.extend_caches(appended.into_iter().skip(1), origin);
+    }
+
+    /// Switches to a different downsampling method. Only the downsampled
+    /// levels are rebuilt, the raw data in the first level is kept.
+    fn set_downsampling_method(&mut self, downsampling_method: DownsamplingMethod) {
+        if self.downsampling_method == downsampling_method {
+            return;
+        }
+
+        self.downsampling_method = downsampling_method;
+        self.cache_levels.truncate(1);
+        self.view_cache = None;
+        self.band_cache = None;
+        self.extend_downsampled_caches();
+    }
+
+    /// Changes how gaps are detected. Since this affects the first cache
+    /// level, all caches are rebuilt on the next update.
+    fn set_gap_threshold(&mut self, gap_threshold: GapThreshold) {
+        if self.gap_threshold != gap_threshold {
+            self.gap_threshold = gap_threshold;
+            self.clear_caches();
         }
     }
 
@@ -164,6 +679,7 @@ impl<
     >(
         &mut self,
         iterator: I,
+        origin: &mut Option<X>,
     ) {
         //println!("updating cache");
         let data = iterator.map(|(x, y)| {
@@ -185,8 +701,11 @@ impl<
         };
 
         if let Some(old) = self.cached_data.as_ref() {
-            if new.len < old.len || new.first_data_point != old.first_data_point {
-                self.rebuild_caches(data);
+            if new.first_data_point != old.first_data_point {
+                self.slide_caches(data, old.len, new.len, origin);
+                self.view_cache = None;
+            } else if new.len < old.len {
+                self.rebuild_caches(data, origin);
             } else {
                 //match (new.len > old.len, new.last_data_point != old.last_data_point) {
                 //    (true, _) => self.extend_caches(data.skip(old.len)),
@@ -194,106 +713,389 @@ impl<
                 //    (false, false) => {}, // We're up to date
                 //}
                 if new.len > old.len {
-                    self.extend_caches(data.skip(old.len));
+                    self.extend_caches(data.skip(old.len), origin);
+                    self.view_cache = None;
                 }
             }
         } else {
-            self.rebuild_caches(data);
+            self.rebuild_caches(data, origin);
         }
 
         self.cached_data = Some(new);
     }
 
-    fn end(&self) -> Option<f64> {
+    /// Moves all cached points by `delta` on the X axis, e.g. after the
+    /// origin of the X axis has changed.
+    fn shift_x(&mut self, delta: f64) {
+        for level in self.cache_levels.iter_mut() {
+            for point in level.points.iter_mut() {
+                point.0 += delta;
+            }
+        }
+        self.last_x = self.last_x.map(|x| x + delta);
+        self.view_cache = None;
+    }
+
+    /// Copies the raw data within the given range into a new line, with X
+    /// axis values relative to `x_offset`.
+    fn snapshot(&self, range: RangeInclusive<f64>, x_offset: f64) -> Self {
+        let points = self.raw_points();
+        let i_begin = points.partition_point(|p| p.0 < *range.start());
+        let i_end = points.partition_point(|p| p.0 <= *range.end()).max(i_begin);
+        let mut snapshot = Self::new(self.downsampling_method);
+        snapshot.extend_points(
+            points[i_begin..i_end]
+                .iter()
+                .map(|(x, y)| (x - x_offset, Some(*y))),
+        );
+        snapshot
+    }
+
+    /// The raw data points, without any downsampling. Gaps are marked by
+    /// NaN values.
+    fn raw_points(&self) -> &[(f64, Y)] {
         self.cache_levels
-            .get(0)
-            .map(|c| c.last())
-            .flatten()
-            .map(|xy| xy.0)
+            .first()
+            .map(|c| c.points.as_slice())
+            .unwrap_or_default()
     }
 
-    fn plot(&mut self, plot_bounds: PlotBounds) -> Vec<[f64; 2]> {
-        if self.cache_levels.len() == 0 {
-            return Vec::new();
-        }
+    /// The value of the raw data at `x`, linearly interpolated between the
+    /// neighbouring points. None outside of the data and within gaps.
+    fn value_at(&self, x: f64) -> Option<f64> {
+        let points = self.raw_points();
+        let i = points.partition_point(|p| p.0 < x);
+        let next = points.get(i)?;
+        let value = if next.0 == x {
+            next.1.into()
+        } else {
+            let previous = points.get(i.checked_sub(1)?)?;
+            let (y0, y1): (f64, f64) = (previous.1.into(), next.1.into());
+            y0 + (y1 - y0) * (x - previous.0) / (next.0 - previous.0)
+        };
+        (!value.is_nan()).then_some(value)
+    }
 
-        // See if we have already plotted those exact bounds last time
-        if let Some((bounds, cached)) = self.view_cache.as_ref() {
-            if bounds.min() == plot_bounds.min() && bounds.max() == plot_bounds.max() {
-                return cached.clone();
-            }
-        }
+    fn end(&self) -> Option<f64> {
+        self.cache_levels
+            .first()
+            .and_then(|c| c.points.last())
+            .map(|xy| xy.0)
+    }
 
-        // If we haven't, try to find the appropriate cache level for the zoom
-        let num_cache_levels = self.cache_levels.len();
-        for (i, cache_level) in self.cache_levels.iter().enumerate() {
-            // find beginning and end for the given plot bounds in the current
-            // cache level by binary search.
-            let (x_min, x_max) = (plot_bounds.min()[0], plot_bounds.max()[0]);
+    /// Finds the coarsest cache level that still has more than `max_points`
+    /// points in view, or the first level if even that has few enough. Returns
+    /// the level and the range of points in view, extended by one point on
+    /// either side.
+    fn visible_level(&self, x_min: f64, x_max: f64, max_points: usize) -> (usize, Range<usize>) {
+        let visible_range = |cache_level: &Vec<(f64, Y)>| {
             let i_begin = usize::max(1, cache_level.partition_point(|v| v.0 < x_min)) - 1;
             let i_end = usize::min(
                 cache_level.partition_point(|v| v.0 <= x_max) + 1,
                 cache_level.len(),
             );
+            i_begin..i_end
+        };
 
-            // If the points in view are few enough, stop and plot them.
-            // If not, keep going down the cache.
-            let points = &cache_level[i_begin..i_end];
-            if points.len() < MAX_POINTS || i == (num_cache_levels - 1) {
-                let mut points: Vec<_> = points.iter().map(|(x, y)| [*x, (*y).into()]).collect();
-
-                // We also add the very first and very last points to the plotted
-                // data, even if they are not visible. This allows egui to
-                // properly initialize the plot and adjust the initial plot bounds
-                // to the plotted data.
-                if i_begin > 0 {
-                    // In order to not upset the auto Y scaling, we only use the
-                    // X axis value and copy the Y axis from the previous first
-                    // instead.
-                    //
-                    // This way we can still zoom in on some detail even if the
-                    // first/last values have vastly different Y axis values.
-                    let previous_first_y = points[0][1];
-                    points.insert(0, [cache_level[0].0, previous_first_y.into()]);
-                }
+        // If the points in view are few enough, stop and plot them. If not,
+        // keep going down the cache.
+        let mut level = 0;
+        let mut range = visible_range(&self.cache_levels[0].points);
+        while range.len() > max_points && level + 1 < self.cache_levels.len() {
+            let next = visible_range(&self.cache_levels[level + 1].points);
+            if next.len() < max_points {
+                break;
+            }
+            (level, range) = (level + 1, next);
+        }
+        (level, range)
+    }
+
+    /// Bins all raw points in view into a histogram, see [histogram]. Unlike
+    /// the cache levels or any subset of the points, this keeps the
+    /// distribution of the data intact.
+    fn density(&self, plot_bounds: PlotBounds, size: [usize; 2]) -> Vec<u32> {
+        let raw = self.raw_points();
+        let i_begin = raw.partition_point(|v| v.0 < plot_bounds.min()[0]);
+        let i_end = raw.partition_point(|v| v.0 <= plot_bounds.max()[0]);
+        histogram(&raw[i_begin..i_end.max(i_begin)], plot_bounds, size)
+    }
+
+    /// Returns the minimum, mean and maximum of the points in each pixel
+    /// column as `[x, min, mean, max]`, or None if [Self::plot] would return
+    /// raw data. Columns containing a gap are all NaN.
+    ///
+    /// The band is computed from the cache level [Self::plot] would use, so
+    /// the minimum and maximum are those of the raw data for
+    /// [DownsamplingMethod::MinMax] and [DownsamplingMethod::M4], give or take
+    /// buckets straddling two columns, while the mean is taken over the
+    /// downsampled points.
+    fn plot_band(
+        &mut self,
+        plot_bounds: PlotBounds,
+        width: f32,
+        points_per_pixel: f32,
+    ) -> Option<Vec<[f64; 4]>> {
+        let raw = self.raw_points();
+        let key = (
+            plot_bounds,
+            width,
+            points_per_pixel,
+            self.downsampling_method,
+            self.revision,
+            raw.first().map(|p| p.0),
+            raw.last().map(|p| p.0),
+        );
+        if let Some((cached_key, cached)) = self.band_cache.as_ref() {
+            if *cached_key == key {
+                return Some(cached.clone());
+            }
+        }
+
+        let (x_min, x_max) = (plot_bounds.min()[0], plot_bounds.max()[0]);
+        let max_points = self
+            .downsampling_method
+            .buckets_for_width(width, points_per_pixel)
+            * self.downsampling_method.points_per_bucket();
+        let i_begin = raw.partition_point(|v| v.0 < x_min);
+        let i_end = raw.partition_point(|v| v.0 <= x_max);
+        if self.downsampling_method == DownsamplingMethod::None
+            || i_end.saturating_sub(i_begin) <= max_points
+        {
+            return None;
+        }
 
-                if cache_level.len() > 1 && i_end < cache_level.len() - 1 && points.len() > 0 {
-                    let previous_last_y = points[points.len() - 1][1];
-                    points.push([cache_level[cache_level.len() - 1].0, previous_last_y]);
+        let columns = width.ceil().max(1.0);
+        let column_width = (x_max - x_min) / columns as f64;
+        let mut band: Vec<[f64; 4]> = Vec::with_capacity(columns as usize + 2);
+        // Column index, minimum, maximum, sum and number of points of the
+        // current column.
+        let mut current: Option<(i64, f64, f64, f64, usize)> = None;
+        let finish = |band: &mut Vec<[f64; 4]>, column: Option<(i64, f64, f64, f64, usize)>| {
+            if let Some((column, min, max, sum, n)) = column {
+                let x = x_min + (column as f64 + 0.5) * column_width;
+                band.push([x, min, sum / n as f64, max]);
+            }
+        };
+        let (level, _) = self.visible_level(x_min, x_max, max_points);
+        let points = &self.cache_levels[level].points;
+        let level_begin = points.partition_point(|v| v.0 < x_min);
+        let level_end = points.partition_point(|v| v.0 <= x_max).max(level_begin);
+        for (x, y) in &points[level_begin..level_end] {
+            // Points right at the end of the view belong to the last column.
+            let column = (((x - x_min) / column_width).floor() as i64).min(columns as i64 - 1);
+            let y: f64 = (*y).into();
+            match &mut current {
+                _ if y.is_nan() => {
+                    finish(&mut band, current.take());
+                    band.push([*x, f64::NAN, f64::NAN, f64::NAN]);
                 }
+                Some((c, min, max, sum, n)) if *c == column => {
+                    (*min, *max, *sum, *n) = (min.min(y), max.max(y), *sum + y, *n + 1);
+                }
+                _ => finish(&mut band, current.replace((column, y, y, y, 1))),
+            }
+        }
+        finish(&mut band, current);
 
-                let points_f64: Vec<_> = points
-                    .into_iter()
-                    .map(|x| [x[0].into(), x[1].into()])
-                    .collect();
+        // Just like in [Self::plot], the very first and last points are
+        // added for the automatic plot bounds, with the Y axis values of
+        // their neighbours.
+        if let (Some(first), Some(last)) = (band.first().copied(), band.last().copied()) {
+            let raw = self.raw_points();
+            if i_begin > 0 {
+                band.insert(0, [raw[0].0, first[1], first[2], first[3]]);
+            }
+            if i_end < raw.len() {
+                band.push([raw[raw.len() - 1].0, last[1], last[2], last[3]]);
+            }
+        }
 
-                //if points.len() < 50 {
-                //    println!("{:?}", points.iter().map(|p| p[0]).collect::<Vec<_>>());
-                //}
+        self.band_cache = Some((key, band.clone()));
+        Some(band)
+    }
+
+    /// Collects the points of the given buckets of an M4 cache level, but
+    /// replaces every bucket that spans more than one column by the points it
+    /// was built from. M4 over the result with the same columns gives the
+    /// same as M4 over the raw data, since the first, minimum, maximum and
+    /// last point of each column, and of each run between gaps, are all kept.
+    fn expand_m4_buckets(
+        &self,
+        level: usize,
+        buckets: Range<usize>,
+        column: &impl Fn(f64) -> i64,
+        out: &mut Vec<(f64, Y)>,
+    ) {
+        let (cache_level, source) = (&self.cache_levels[level], &self.cache_levels[level - 1]);
+        for bucket in buckets {
+            let points = &cache_level.points[cache_level.points_of(bucket..bucket + 1)];
+            let Some((first, last)) = points.first().zip(points.last()) else {
+                continue;
+            };
+            if column(first.0) == column(last.0) {
+                out.extend_from_slice(points);
+                continue;
+            }
+
+            let range = cache_level.bucket_range(bucket, source.points.len());
+            if level == 1 {
+                out.extend_from_slice(&source.points[range]);
+            } else if !range.is_empty() {
+                let buckets = source.bucket_of(range.start)..source.bucket_of(range.end - 1) + 1;
+                self.expand_m4_buckets(level - 1, buckets, column, out);
+            }
+        }
+    }
+
+    fn plot(
+        &mut self,
+        plot_bounds: PlotBounds,
+        width: f32,
+        points_per_pixel: f32,
+    ) -> Vec<[f64; 2]> {
+        if self.cache_levels.is_empty() {
+            return Vec::new();
+        }
+
+        // See if we have already plotted those exact bounds last time
+        if let Some((bounds, cached_width, cached)) = self.view_cache.as_ref() {
+            if bounds.min() == plot_bounds.min()
+                && bounds.max() == plot_bounds.max()
+                && *cached_width == width
+            {
+                return cached.clone();
+            }
+        }
 
-                self.view_cache = Some((plot_bounds, points_f64.clone()));
-                return points_f64;
+        let (x_min, x_max) = (plot_bounds.min()[0], plot_bounds.max()[0]);
+        let buckets = self
+            .downsampling_method
+            .buckets_for_width(width, points_per_pixel);
+        let max_points = buckets * self.downsampling_method.points_per_bucket();
+
+        // If we haven't, find the coarsest cache level that still has more
+        // points in view than we want to draw.
+        let (level, visible_range) = self.visible_level(x_min, x_max, max_points);
+        let (i_begin, i_end) = (visible_range.start, visible_range.end);
+
+        let cache_level = &self.cache_levels[level].points;
+        let visible = &cache_level[i_begin..i_end];
+        let m4 = self.downsampling_method == DownsamplingMethod::M4;
+        // Without downsampling, all visible points are drawn as they are.
+        let decimate = self.downsampling_method != DownsamplingMethod::None
+            && (visible.len() > max_points || (m4 && level > 0));
+        let mut points: Vec<[f64; 2]> = if decimate {
+            // Final decimation step, reducing the data to the points we
+            // actually want to draw for the given width.
+            let mut decimated = Vec::with_capacity(max_points + 2);
+            let bucket_width = (x_max - x_min) / buckets as f64;
+            if m4 && level > 0 {
+                // Cached buckets don't line up with the final ones, so those
+                // spanning more than one are replaced by their source data.
+                let column = |x: f64| ((x - x_min) / bucket_width).floor() as i64;
+                let cached = &self.cache_levels[level];
+                let mut expanded = Vec::with_capacity(2 * max_points);
+                self.expand_m4_buckets(
+                    level,
+                    cached.bucket_of(i_begin)..cached.bucket_of(i_end - 1) + 1,
+                    &column,
+                    &mut expanded,
+                );
+                self.downsampling_method.downsample_by_x(
+                    &expanded,
+                    x_min,
+                    bucket_width,
+                    &mut decimated,
+                );
+            } else {
+                self.downsampling_method.downsample_by_x(
+                    visible,
+                    x_min,
+                    bucket_width,
+                    &mut decimated,
+                );
             }
+            decimated.iter().map(|(x, y)| [*x, (*y).into()]).collect()
+        } else {
+            visible.iter().map(|(x, y)| [*x, (*y).into()]).collect()
+        };
+
+        // We also add the very first and very last points to the plotted
+        // data, even if they are not visible. This allows egui to
+        // properly initialize the plot and adjust the initial plot bounds
+        // to the plotted data.
+        if i_begin > 0 && !points.is_empty() {
+            // In order to not upset the auto Y scaling, we only use the
+            // X axis value and copy the Y axis from the previous first
+            // instead.
+            //
+            // This way we can still zoom in on some detail even if the
+            // first/last values have vastly different Y axis values.
+            let previous_first_y = points[0][1];
+            points.insert(0, [cache_level[0].0, previous_first_y]);
+        }
+
+        if cache_level.len() > 1 && i_end < cache_level.len() - 1 && !points.is_empty() {
+            let previous_last_y = points[points.len() - 1][1];
+            points.push([cache_level[cache_level.len() - 1].0, previous_last_y]);
         }
 
-        Vec::new()
+        //if points.len() < 50 {
+        //    println!("{:?}", points.iter().map(|p| p[0]).collect::<Vec<_>>());
+        //}
+
+        self.view_cache = Some((plot_bounds, width, points.clone()));
+        points
     }
 }
 
-pub struct TimeseriesGroup {
+/// A snapshot of a line, drawn as a dimmed overlay.
+#[derive(Debug)]
+struct Reference<X, Y> {
+    // Id of the line the reference was captured from, if any.
+    source: Option<String>,
+    // Points are stored with their X axis values as displayed when captured.
+    line: TimeseriesLineMemory<X, Y>,
+    x_offset: f64,
+}
+
+/// A copy of the data of all lines around a trigger event.
+#[derive(Debug)]
+struct Segment<X, Y> {
+    trigger_point: f64,
+    // Points are stored relative to the trigger event.
+    lines: HashMap<String, TimeseriesLineMemory<X, Y>>,
+}
+
+pub struct TimeseriesGroup<X> {
     pub(crate) link_group_name: String,
     pub(crate) link_y: bool,
     pub(crate) last_view_width: Option<f64>,
+    pub(crate) x_axis_origin: Option<X>,
 }
 
-impl TimeseriesGroup {
+impl<X: TimeseriesXAxis> TimeseriesGroup<X> {
     pub fn new(name: impl ToString, link_y: bool) -> Self {
         Self {
             link_group_name: name.to_string(),
             link_y,
             last_view_width: None,
+            x_axis_origin: None,
         }
     }
+
+    /// Set the value that is drawn as zero on the X axis for all plots in
+    /// this group. If not set, the origin of the first plot that has one is
+    /// used for the entire group.
+    pub fn set_origin(&mut self, origin: X) {
+        self.x_axis_origin = Some(origin);
+    }
+
+    /// Returns the value that is drawn as zero on the X axis, if known.
+    pub fn origin(&self) -> Option<&X> {
+        self.x_axis_origin.as_ref()
+    }
 }
 
 /// Main memory object for a timeseries plot.
@@ -324,6 +1126,13 @@ impl TimeseriesGroup {
 ///   This should be recognized automatically. However, if you add and delete
 ///   the same number of values in a single frame, this would be equivalent to...
 ///
+/// - ### Deleting values from the start while appending new ones
+///
+///   This is the common ring buffer pattern, e.g. using a
+///   [std::collections::VecDeque] as a sliding window. It is recognized
+///   automatically, and the caches are trimmed and extended in place instead
+///   of being rebuilt.
+///
 /// - ### Modifying values without changing the number of points
 ///
 ///   These changes may be missed, depending on which points are changed.
@@ -335,10 +1144,44 @@ impl TimeseriesGroup {
 pub struct TimeseriesPlotMemory<X, Y> {
     pub(crate) id: egui::Id,
     lines: HashMap<String, TimeseriesLineMemory<X, Y>>,
-    downsampling_method: DownsamplingMethod,
     pub(crate) reset_auto_bounds_next_frame: bool,
     pub(crate) last_view_width: f64,
+    pub(crate) follow_duration: Option<f64>,
     pub(crate) last_auto_bounds: bool,
+    pub(crate) points_per_pixel: f32,
+    x_axis_origin: Option<X>,
+    trigger_state: Option<TriggerState>,
+    acquisition_mode: AcquisitionMode,
+    // End of the data at the time the acquisition was stopped, if it is.
+    stopped_at: Option<f64>,
+    // Copy of the data around the view at the time the acquisition was
+    // stopped, which is shown instead of the lines until it is resumed.
+    frozen: HashMap<String, TimeseriesLineMemory<X, Y>>,
+    // End of the data at the time a single acquisition was started.
+    single_armed_at: Option<f64>,
+    // X axis value drawn at zero while pinned to a trigger event.
+    pub(crate) display_point: Option<f64>,
+    pub(crate) cursors: Cursors,
+    // In the order they were added, so math lines can depend on each other.
+    math_lines: Vec<(String, MathLine)>,
+    references: Vec<(String, Reference<X, Y>)>,
+    pub(crate) mask_state: Option<MaskState>,
+    accumulation: Option<Accumulation>,
+    // Accumulated segments for every line, along with the resulting traces.
+    accumulators: HashMap<String, (Accumulator, Vec<TimeseriesLineMemory<X, Y>>)>,
+    // Trigger event of the last accumulated segments.
+    accumulated_at: Option<f64>,
+    segment_capacity: usize,
+    segments: VecDeque<Segment<X, Y>>,
+    // Trigger events found by the last trigger update, not stored as
+    // segments yet.
+    new_trigger_events: Vec<f64>,
+    // Segment shown instead of the current acquisition, if any.
+    selected_segment: Option<usize>,
+    pub(crate) overlay_segments: bool,
+    persistence: HashMap<String, PersistenceState>,
+    // Density plots of every line, for each section of the view.
+    density: HashMap<(String, usize), DensityState>,
 }
 
 impl<
@@ -351,10 +1194,32 @@ impl<
         Self {
             id: id.into(),
             lines: HashMap::new(),
-            downsampling_method: DownsamplingMethod::MinMax,
             reset_auto_bounds_next_frame: true,
             last_view_width: 10.0,
+            follow_duration: None,
             last_auto_bounds: true,
+            points_per_pixel: DEFAULT_POINTS_PER_PIXEL,
+            x_axis_origin: None,
+            trigger_state: None,
+            acquisition_mode: AcquisitionMode::default(),
+            stopped_at: None,
+            frozen: HashMap::new(),
+            single_armed_at: None,
+            display_point: None,
+            cursors: Cursors::default(),
+            math_lines: Vec::new(),
+            references: Vec::new(),
+            mask_state: None,
+            accumulation: None,
+            accumulators: HashMap::new(),
+            accumulated_at: None,
+            segment_capacity: 0,
+            segments: VecDeque::new(),
+            new_trigger_events: Vec::new(),
+            selected_segment: None,
+            overlay_segments: false,
+            persistence: HashMap::new(),
+            density: HashMap::new(),
         }
     }
 
@@ -397,33 +1262,1614 @@ impl<
         if !self.lines.contains_key(line_id) {
             self.lines.insert(
                 line_id.clone(),
-                TimeseriesLineMemory::new(self.downsampling_method),
+                TimeseriesLineMemory::new(DownsamplingMethod::default()),
             );
         }
 
         self.lines
             .get_mut(line_id)
             .unwrap()
-            .update_cache(line_iterator);
+            .update_cache(line_iterator, &mut self.x_axis_origin);
     }
 
-    /// Returns the data to be plotted for the given line and current plot bounds.
+    /// Set the value that is drawn as zero on the X axis, shared by all lines
+    /// of this plot. Values before the origin are drawn at negative offsets.
     ///
-    /// Called by [crate::TimeseriesPlot] when needed.
-    pub fn plot(&mut self, line_id: &String, plot_bounds: PlotBounds) -> Vec<[f64; 2]> {
-        self.lines
-            .get_mut(line_id)
-            .map(|l| l.plot(plot_bounds))
-            .unwrap_or_default()
+    /// If not set, the first value that is plotted is used as the origin
+    /// for X axis types that need one, like [std::time::Instant].
+    pub fn set_origin(&mut self, origin: X) {
+        if self.x_axis_origin.as_ref() == Some(&origin) {
+            return;
+        }
+
+        // Instead of rebuilding the caches, move the already cached points
+        // by the distance between the old and the new origin. Without an
+        // origin, X axis types like f64 are drawn relative to their zero.
+        let delta = match self.x_axis_origin.take() {
+            Some(old_origin) => old_origin.to_f64(&mut Some(origin.clone())),
+            None => -origin.clone().to_f64(&mut None),
+        };
+        if delta != 0.0 {
+            for line in self.lines.values_mut() {
+                line.shift_x(delta);
+            }
+            if let Some(trigger_state) = self.trigger_state.as_mut() {
+                trigger_state.shift_x(delta);
+            }
+            if let Some(mask_state) = self.mask_state.as_mut() {
+                mask_state.shift_x(delta);
+            }
+            for x in [
+                &mut self.stopped_at,
+                &mut self.single_armed_at,
+                &mut self.display_point,
+                &mut self.accumulated_at,
+            ] {
+                *x = x.map(|x| x + delta);
+            }
+            for segment in self.segments.iter_mut() {
+                segment.trigger_point += delta;
+            }
+            for event in self.new_trigger_events.iter_mut() {
+                *event += delta;
+            }
+            for line in self.frozen.values_mut() {
+                line.shift_x(delta);
+            }
+        }
+
+        self.x_axis_origin = Some(origin);
     }
 
-    /// Returns the current last known X axis value, if any are present.
+    /// Returns the value that is drawn as zero on the X axis, if known.
+    pub fn origin(&self) -> Option<&X> {
+        self.x_axis_origin.as_ref()
+    }
+
+    /// Makes this plot and the given group agree on the X axis origin. The
+    /// origin of the group takes precedence.
+    pub(crate) fn sync_origin(&mut self, group: &mut TimeseriesGroup<X>) {
+        match (&group.x_axis_origin, &self.x_axis_origin) {
+            (Some(origin), _) => self.set_origin(origin.clone()),
+            (None, Some(origin)) => group.x_axis_origin = Some(origin.clone()),
+            (None, None) => {}
+        }
+    }
+
+    /// Set the downsampling method used for the given line.
+    ///
+    /// If the method differs from the one currently in use, the downsampled
+    /// caches of this line are rebuilt. Other lines are not affected.
+    ///
+    /// Called by [crate::TimeseriesPlot] with the method configured via
+    /// [crate::TimeseriesLine::downsampling].
+    pub fn set_downsampling_method(
+        &mut self,
+        line_id: &str,
+        downsampling_method: DownsamplingMethod,
+    ) {
+        if let Some(line) = self.lines.get_mut(line_id) {
+            line.set_downsampling_method(downsampling_method);
+        } else {
+            self.lines.insert(
+                line_id.to_string(),
+                TimeseriesLineMemory::new(downsampling_method),
+            );
+        }
+    }
+
+    /// Set how many points are plotted per pixel of plot width, before
+    /// downsampling kicks in. Higher values give more detail at the cost of
+    /// performance. Defaults to 2, i.e. one min/max pair per pixel.
+    ///
+    /// Downsampled lines get one bucket per min/max pair, so with
+    /// [DownsamplingMethod::Mean] and [DownsamplingMethod::Lttb] half as
+    /// many points are drawn, and with [DownsamplingMethod::M4] twice as many.
+    pub fn set_points_per_pixel(&mut self, points_per_pixel: f32) {
+        if self.points_per_pixel != points_per_pixel {
+            self.points_per_pixel = points_per_pixel;
+            for line in self.lines.values_mut() {
+                line.view_cache = None;
+            }
+        }
+    }
+
+    /// Set how gaps are detected for the given line, in addition to missing
+    /// values. If the setting differs from the one currently in use, the
+    /// caches of this line are rebuilt.
+    ///
+    /// Called by [crate::TimeseriesPlot] with the threshold configured via
+    /// [crate::TimeseriesLine::gap_threshold].
+    pub fn set_gap_threshold(&mut self, line_id: &str, gap_threshold: GapThreshold) {
+        if let Some(line) = self.lines.get_mut(line_id) {
+            line.set_gap_threshold(gap_threshold);
+        } else {
+            let mut line = TimeseriesLineMemory::new(DownsamplingMethod::default());
+            line.gap_threshold = gap_threshold;
+            self.lines.insert(line_id.to_string(), line);
+        }
+    }
+
+    /// Define a math line, computed from other lines. It can be drawn,
+    /// measured and used as a source for other math lines like any other
+    /// line, using the given id. If the function differs from the one
+    /// currently in use, the line is recomputed.
+    ///
+    /// Called by [crate::TimeseriesPlot::math_line].
+    pub fn set_math_line(&mut self, line_id: &str, function: MathFunction) {
+        match self.math_lines.iter_mut().find(|(id, _)| id == line_id) {
+            Some((_, math_line)) if math_line.function == function => {}
+            Some((_, math_line)) => *math_line = MathLine::new(function),
+            None => {
+                self.math_lines
+                    .push((line_id.to_string(), MathLine::new(function)));
+            }
+        }
+    }
+
+    /// Remove a math line, along with its cached data.
+    pub fn remove_math_line(&mut self, line_id: &str) {
+        self.math_lines.retain(|(id, _)| id != line_id);
+        self.lines.remove(line_id);
+    }
+
+    /// Computes the points of all math lines that have been added to their
+    /// source lines since the last update. If a source line has been
+    /// rebuilt, the math line is recomputed from scratch.
+    ///
+    /// Called by [crate::TimeseriesPlot] before drawing.
+    pub fn update_math_lines(&mut self) {
+        #[cfg(feature = "profiling")]
+        puffin::profile_function!();
+
+        for (id, math_line) in self.math_lines.iter_mut() {
+            // Taken out of the map while updating, so the sources can be
+            // borrowed at the same time.
+            let mut line = self
+                .lines
+                .remove(id.as_str())
+                .unwrap_or_else(|| TimeseriesLineMemory::new(DownsamplingMethod::default()));
+
+            let (a_id, b_id) = math_line.function.sources();
+            let (a, b) = match (self.lines.get(a_id), b_id.map(|id| self.lines.get(id))) {
+                (Some(a), None) => (a, None),
+                (Some(a), Some(Some(b))) => (a, Some(b)),
+                _ => {
+                    self.lines.insert(id.clone(), line);
+                    continue;
+                }
+            };
+            let revisions = (a.revision, b.map(|b| b.revision));
+            if math_line.source_revisions != Some(revisions) || line.end().is_none() {
+                line.clear_caches();
+                math_line.source_revisions = Some(revisions);
+                math_line.integral = 0.0;
+            }
+
+            // Continue after the last computed point. With two sources, only
+            // compute points that can be interpolated on the second one.
+            let a_points = a.raw_points();
+            let start = line
+                .last_x
+                .map_or(0, |last_x| a_points.partition_point(|p| p.0 <= last_x));
+            let limit = b.map_or(f64::INFINITY, |b| b.end().unwrap_or(f64::NEG_INFINITY));
+            let end = a_points.partition_point(|p| p.0 <= limit).max(start);
+
+            if start < end {
+                let as_f64 = |(x, y): (f64, Y)| (x, y.into());
+                let points: Vec<_> = (start..end)
+                    .map(|i| {
+                        let point = as_f64(a_points[i]);
+                        let previous = i.checked_sub(1).map(|i| as_f64(a_points[i]));
+                        let b_value = b.and_then(|b| b.value_at(point.0));
+                        let value = math_line.compute(point, previous, b_value);
+                        (point.0, value.and_then(num_traits::cast::<f64, Y>))
+                    })
+                    .collect();
+                line.extend_points(points.into_iter());
+                line.view_cache = None;
+            }
+
+            // Follow data that has been removed from the start of the source.
+            if let Some(first) = a_points.first() {
+                let evict = line.raw_points().partition_point(|p| p.0 < first.0);
+                if evict > 0 {
+                    line.evict_front(evict);
+                    line.view_cache = None;
+                }
+            }
+
+            self.lines.insert(id.clone(), line);
+        }
+    }
+
+    /// Capture the data of the given line as a reference with the given name,
+    /// replacing any reference of the same name. X axis values are stored as
+    /// displayed, i.e. relative to the trigger event when triggered.
+    ///
+    /// Returns false if there is no line with the given id.
+    pub fn capture_reference(&mut self, line_id: &str, name: impl ToString) -> bool {
+        let Some(line) = self.displayed_line(line_id) else {
+            return false;
+        };
+
+        let display_point = self.display_point.unwrap_or_default();
+        let reference = line.snapshot(f64::NEG_INFINITY..=f64::INFINITY, display_point);
+
+        self.insert_reference(name.to_string(), Some(line_id.to_string()), reference);
+        true
+    }
+
+    fn insert_reference(
+        &mut self,
+        name: String,
+        source: Option<String>,
+        line: TimeseriesLineMemory<X, Y>,
+    ) {
+        let reference = Reference {
+            source,
+            line,
+            x_offset: 0.0,
+        };
+        match self.references.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = reference,
+            None => self.references.push((name, reference)),
+        }
+    }
+
+    /// Returns the names of all references, in the order they were added.
+    pub fn references(&self) -> impl Iterator<Item = &str> {
+        self.references.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn remove_reference(&mut self, name: &str) {
+        self.references.retain(|(n, _)| n != name);
+    }
+
+    /// Move the given reference on the X axis, e.g. to line it up with the
+    /// current data.
+    pub fn set_reference_offset(&mut self, name: &str, x_offset: f64) {
+        if let Some((_, reference)) = self.references.iter_mut().find(|(n, _)| n == name) {
+            reference.x_offset = x_offset;
+        }
+    }
+
+    pub fn reference_offset(&self, name: &str) -> Option<f64> {
+        self.references
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, r)| r.x_offset)
+    }
+
+    /// Save the given reference to a CSV file, without its offset.
+    pub fn save_reference<P: AsRef<Path>>(&self, name: &str, path: P) -> std::io::Result<()> {
+        let Some((_, reference)) = self.references.iter().find(|(n, _)| n == name) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no reference named {:?}", name),
+            ));
+        };
+        save_points(
+            path,
+            reference
+                .line
+                .raw_points()
+                .iter()
+                .map(|(x, y)| (*x, (*y).into())),
+        )
+    }
+
+    /// Load a reference saved with [TimeseriesPlotMemory::save_reference],
+    /// replacing any reference of the same name.
+    pub fn load_reference<P: AsRef<Path>>(
+        &mut self,
+        name: impl ToString,
+        path: P,
+    ) -> std::io::Result<()> {
+        let points = load_points(path)?;
+        let mut line = TimeseriesLineMemory::new(DownsamplingMethod::default());
+        line.extend_points(
+            points
+                .into_iter()
+                .map(|(x, y)| (x, num_traits::cast::<f64, Y>(y))),
+        );
+        self.insert_reference(name.to_string(), None, line);
+        Ok(())
+    }
+
+    /// Returns the names of all references, along with the id of the line
+    /// they were captured from, if known.
+    pub(crate) fn reference_sources(&self) -> Vec<(String, Option<String>)> {
+        self.references
+            .iter()
+            .map(|(name, r)| (name.clone(), r.source.clone()))
+            .collect()
+    }
+
+    /// Returns the data to be plotted for the given reference, like
+    /// [TimeseriesPlotMemory::plot]. Points far outside of the plot bounds
+    /// are left out, so references don't affect the automatic plot bounds.
+    pub(crate) fn plot_reference(
+        &mut self,
+        name: &str,
+        plot_bounds: PlotBounds,
+        width: f32,
+    ) -> Vec<[f64; 2]> {
+        let points_per_pixel = self.points_per_pixel;
+        let Some((_, reference)) = self.references.iter_mut().find(|(n, _)| n == name) else {
+            return Vec::new();
+        };
+
+        let offset = reference.x_offset;
+        let (x_min, x_max) = (plot_bounds.min()[0] - offset, plot_bounds.max()[0] - offset);
+        let bounds =
+            PlotBounds::from_min_max([x_min, plot_bounds.min()[1]], [x_max, plot_bounds.max()[1]]);
+        let points = reference.line.plot(bounds, width, points_per_pixel);
+        let begin = points.partition_point(|p| p[0] < x_min).saturating_sub(1);
+        let end = usize::min(points.partition_point(|p| p[0] <= x_max) + 1, points.len());
+        points[begin..end.max(begin)]
+            .iter()
+            .map(|p| [p[0] + offset, p[1]])
+            .collect()
+    }
+
+    /// Returns the data to be plotted for the given line and current plot
+    /// bounds, for a plot that is `width` pixels wide.
+    ///
+    /// Called by [crate::TimeseriesPlot] when needed.
+    pub fn plot(&mut self, line_id: &str, plot_bounds: PlotBounds, width: f32) -> Vec<[f64; 2]> {
+        let points_per_pixel = self.points_per_pixel;
+        self.displayed_line_mut(line_id)
+            .map(|l| l.plot(plot_bounds, width, points_per_pixel))
+            .unwrap_or_default()
+    }
+
+    /// Looks for new events of the given trigger, and returns the X axis
+    /// value of the most recent one. Only events followed by at least
+    /// `post_trigger` of data are taken into account.
+    ///
+    /// Called by [crate::TimeseriesPlot] if a trigger is set.
+    pub fn update_trigger(&mut self, trigger: &Trigger, post_trigger: f64) -> Option<f64> {
+        if self.trigger_state.as_ref().map(|s| &s.trigger) != Some(trigger) {
+            self.trigger_state = Some(TriggerState::new(trigger.clone()));
+        }
+
+        let state = self.trigger_state.as_mut().unwrap();
+        if let Some(line) = self.lines.get(&trigger.line_id) {
+            self.new_trigger_events = state.update(line.raw_points(), post_trigger);
+        }
+        state.last_trigger
+    }
+
+    /// Updates the trigger and acquisition state for the next frame. Returns
+    /// the X axis value that should be drawn at zero, if the view is pinned
+    /// to a trigger event, or free-running in [AcquisitionMode::Auto].
+    ///
+    /// While stopped, the view is frozen and trigger events are not updated.
+    ///
+    /// Called by [crate::TimeseriesPlot] if a trigger is set.
+    pub fn acquire(&mut self, trigger: &Trigger, post_trigger: f64) -> Option<f64> {
+        if self.stopped_at.is_some() {
+            return self.display_point;
+        }
+
+        let trigger_point = self.update_trigger(trigger, post_trigger);
+        match self.acquisition_mode {
+            AcquisitionMode::Auto => {
+                // Free-run if there hasn't been a trigger event for a full view.
+                let free_run = self.end().map(|end| end - post_trigger);
+                self.display_point = match (trigger_point, free_run) {
+                    (Some(t), Some(f)) if f - t > post_trigger => free_run,
+                    (None, _) => free_run,
+                    _ => trigger_point,
+                };
+            }
+            AcquisitionMode::Normal => self.display_point = trigger_point,
+            AcquisitionMode::Single => {
+                let armed_at = self.single_armed_at.unwrap_or(f64::NEG_INFINITY);
+                if let Some(t) = trigger_point.filter(|t| *t > armed_at) {
+                    self.display_point = Some(t);
+                    self.stop();
+                }
+            }
+        }
+
+        self.display_point
+    }
+
+    /// Tests the current acquisition against the given mask, if it hasn't
+    /// been tested yet and enough data is available. For masks relative to
+    /// the trigger, only views pinned to a trigger event count as
+    /// acquisitions, not the free-running view of [AcquisitionMode::Auto].
+    /// Absolute masks are tested once for every revision of the line data.
+    /// A different mask resets the counts.
+    ///
+    /// Called by [crate::TimeseriesPlot] if a mask is set, after
+    /// [TimeseriesPlotMemory::acquire].
+    pub fn update_mask(&mut self, mask: &Mask) {
+        if self.mask_state.as_ref().map(|s| &s.mask) != Some(mask) {
+            self.mask_state = Some(MaskState::new(mask.clone()));
+        }
+
+        let reference = match mask.reference {
+            MaskReference::Trigger => self
+                .display_point
+                .filter(|p| Some(*p) == self.trigger_point()),
+            MaskReference::Absolute => Some(0.0),
+        };
+        let state = self.mask_state.as_mut().unwrap();
+        let (Some(reference), Some(line), Some((_, end))) =
+            (reference, self.lines.get(&mask.line_id), mask.span())
+        else {
+            return;
+        };
+        let tested = match mask.reference {
+            MaskReference::Trigger => state.tested_at == Some(reference),
+            MaskReference::Absolute => state.tested_revision == Some(line.revision),
+        };
+        let covered = line
+            .end()
+            .is_some_and(|line_end| line_end >= reference + end);
+        if !tested && covered {
+            state.test(line.raw_points(), reference, line.revision);
+        }
+    }
+
+    /// Returns how many acquisitions have passed or failed the mask test.
+    pub fn mask_counts(&self) -> MaskCounts {
+        self.mask_state
+            .as_ref()
+            .map(|s| s.counts)
+            .unwrap_or_default()
+    }
+
+    /// Start counting passed and failed acquisitions from zero.
+    pub fn reset_mask_counts(&mut self) {
+        if let Some(state) = self.mask_state.as_mut() {
+            state.counts = MaskCounts::default();
+        }
+    }
+
+    /// Combine the segments around consecutive trigger events, e.g. to average
+    /// out noise. While triggered, the combined traces are shown instead of
+    /// the data of each line. A different setting starts over.
+    pub fn set_accumulation(&mut self, accumulation: Option<Accumulation>) {
+        if self.accumulation != accumulation {
+            self.accumulation = accumulation;
+            self.reset_accumulation();
+        }
+    }
+
+    pub fn accumulation(&self) -> Option<Accumulation> {
+        self.accumulation
+    }
+
+    /// Discard all accumulated segments.
+    pub fn reset_accumulation(&mut self) {
+        self.accumulators.clear();
+        self.accumulated_at = None;
+    }
+
+    /// Adds the segments around the current trigger event to the
+    /// accumulation, if it hasn't been added yet. Segments span from
+    /// `pre_trigger` before to `post_trigger` after the event. Free-running
+    /// acquisitions without a trigger event are not accumulated.
+    ///
+    /// Called by [crate::TimeseriesPlot] if a trigger is set, after
+    /// [TimeseriesPlotMemory::acquire].
+    pub fn update_accumulation(&mut self, pre_trigger: f64, post_trigger: f64) {
+        let Some(accumulation) = self.accumulation else {
+            return;
+        };
+        let Some(trigger_point) = self
+            .display_point
+            .filter(|p| Some(*p) == self.trigger_point())
+        else {
+            return;
+        };
+        if self.accumulated_at == Some(trigger_point) {
+            return;
+        }
+        self.accumulated_at = Some(trigger_point);
+
+        let window = (-pre_trigger, post_trigger);
+        for (id, line) in self.lines.iter() {
+            let (accumulator, traces) = match self.accumulators.get_mut(id) {
+                Some((accumulator, traces)) if accumulator.window == window => {
+                    (accumulator, traces)
+                }
+                _ => {
+                    // Use about as many positions as there are samples in the
+                    // first segment, which may not be complete yet.
+                    let points = line.raw_points();
+                    let start = f64::max(
+                        trigger_point - pre_trigger,
+                        points.first().map_or(f64::NEG_INFINITY, |p| p.0),
+                    );
+                    let end = trigger_point + post_trigger;
+                    let samples = points.partition_point(|p| p.0 <= end)
+                        - points.partition_point(|p| p.0 < start);
+                    let len = (samples as f64 * (pre_trigger + post_trigger) / (end - start))
+                        .round() as usize;
+                    let accumulator = Accumulator::new(accumulation, window, len);
+                    self.accumulators
+                        .insert(id.clone(), (accumulator, Vec::new()));
+                    let (accumulator, traces) = self.accumulators.get_mut(id).unwrap();
+                    (accumulator, traces)
+                }
+            };
+
+            accumulator.add(|x| line.value_at(trigger_point + x));
+
+            let positions: Vec<f64> = accumulator.positions().collect();
+            let values = accumulator.traces();
+            traces.resize_with(values.len(), || {
+                TimeseriesLineMemory::new(line.downsampling_method)
+            });
+            for (trace, values) in traces.iter_mut().zip(values) {
+                trace.clear_caches();
+                trace.extend_points(
+                    positions
+                        .iter()
+                        .zip(values)
+                        .map(|(x, y)| (*x, num_traits::cast::<f64, Y>(y))),
+                );
+            }
+        }
+    }
+
+    /// Returns the accumulated traces to be plotted for the given line,
+    /// relative to the trigger event, separated by gaps. Returns None if
+    /// nothing has been accumulated for this line.
+    pub(crate) fn plot_accumulated(
+        &mut self,
+        line_id: &str,
+        plot_bounds: PlotBounds,
+        width: f32,
+    ) -> Option<Vec<[f64; 2]>> {
+        let points_per_pixel = self.points_per_pixel;
+        let (_, traces) = self.accumulators.get_mut(line_id)?;
+        let mut points = Vec::new();
+        for trace in traces.iter_mut() {
+            if !points.is_empty() {
+                points.push([f64::NAN, f64::NAN]);
+            }
+            points.extend(trace.plot(plot_bounds, width, points_per_pixel));
+        }
+        Some(points)
+    }
+
+    /// Keep a copy of the data around each of the last `capacity` trigger
+    /// events. The oldest segments are dropped when the capacity is reached.
+    /// Defaults to 0, i.e. no segments are kept.
+    pub fn set_segment_capacity(&mut self, capacity: usize) {
+        self.segment_capacity = capacity;
+        while self.segments.len() > capacity {
+            self.pop_segment();
+        }
+    }
+
+    pub fn segment_capacity(&self) -> usize {
+        self.segment_capacity
+    }
+
+    fn pop_segment(&mut self) {
+        self.segments.pop_front();
+        self.selected_segment = match self.selected_segment {
+            Some(0) if self.segments.is_empty() => None,
+            Some(i) => Some(i.saturating_sub(1)),
+            None => None,
+        };
+    }
+
+    /// Stores the data around every trigger event found since the last call
+    /// as a segment, including events that were never displayed because a
+    /// newer one arrived in the same frame. Segments span from `pre_trigger`
+    /// before to `post_trigger` after the event. Free-running acquisitions
+    /// without a trigger event are not stored.
+    ///
+    /// Called by [crate::TimeseriesPlot] if a trigger is set, after
+    /// [TimeseriesPlotMemory::acquire].
+    pub fn update_segments(&mut self, pre_trigger: f64, post_trigger: f64) {
+        let events = std::mem::take(&mut self.new_trigger_events);
+        // Events that would be dropped right away are not stored at all.
+        let skip = events.len().saturating_sub(self.segment_capacity);
+        for trigger_point in events.into_iter().skip(skip) {
+            if self
+                .segments
+                .back()
+                .is_some_and(|s| s.trigger_point >= trigger_point)
+            {
+                continue;
+            }
+
+            let range = trigger_point - pre_trigger..=trigger_point + post_trigger;
+            let lines = self
+                .lines
+                .iter()
+                .map(|(id, line)| (id.clone(), line.snapshot(range.clone(), trigger_point)))
+                .collect();
+
+            self.segments.push_back(Segment {
+                trigger_point,
+                lines,
+            });
+            if self.segments.len() > self.segment_capacity {
+                self.pop_segment();
+            }
+        }
+    }
+
+    /// Returns the number of stored segments.
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns the X axis value of the trigger event of the given segment,
+    /// counting from the oldest one.
+    pub fn segment_trigger_point(&self, index: usize) -> Option<f64> {
+        self.segments.get(index).map(|s| s.trigger_point)
+    }
+
+    /// Show the given segment instead of the current acquisition, or the
+    /// current acquisition again if None.
+    pub fn select_segment(&mut self, index: Option<usize>) {
+        self.selected_segment = index.filter(|i| *i < self.segments.len());
+    }
+
+    pub fn selected_segment(&self) -> Option<usize> {
+        self.selected_segment
+    }
+
+    /// Draw all stored segments as a dimmed overlay.
+    pub fn set_overlay_segments(&mut self, overlay: bool) {
+        self.overlay_segments = overlay;
+    }
+
+    pub fn overlay_segments(&self) -> bool {
+        self.overlay_segments
+    }
+
+    pub fn clear_segments(&mut self) {
+        self.segments.clear();
+        self.selected_segment = None;
+    }
+
+    /// Save the given line of the given segment to a CSV file, with X axis
+    /// values relative to the trigger event. The file can be loaded as a
+    /// reference with [TimeseriesPlotMemory::load_reference].
+    pub fn export_segment<P: AsRef<Path>>(
+        &self,
+        index: usize,
+        line_id: &str,
+        path: P,
+    ) -> std::io::Result<()> {
+        let Some(line) = self.segments.get(index).and_then(|s| s.lines.get(line_id)) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no line {:?} in segment {}", line_id, index),
+            ));
+        };
+        save_points(
+            path,
+            line.raw_points().iter().map(|(x, y)| (*x, (*y).into())),
+        )
+    }
+
+    /// Returns the data to be plotted for the given line of the given
+    /// segment, or of the selected segment if None, relative to its trigger
+    /// event.
+    pub(crate) fn plot_segment(
+        &mut self,
+        index: Option<usize>,
+        line_id: &str,
+        plot_bounds: PlotBounds,
+        width: f32,
+    ) -> Option<Vec<[f64; 2]>> {
+        let points_per_pixel = self.points_per_pixel;
+        let segment = self.segments.get_mut(index.or(self.selected_segment)?)?;
+        let line = segment.lines.get_mut(line_id)?;
+        Some(line.plot(plot_bounds, width, points_per_pixel))
+    }
+
+    /// Adds the points drawn for the given line to its persistence buffer, if
+    /// they belong to a new acquisition, and returns the texture showing the
+    /// buffer along with the bounds it covers. Acquisitions are identified by
+    /// their trigger event or, without a trigger, by the end of the data. The
+    /// buffer starts over whenever the plot bounds or size change.
+    ///
+    /// Called by [crate::TimeseriesPlot] if persistence is enabled.
+    pub(crate) fn update_persistence(
+        &mut self,
+        ctx: &egui::Context,
+        line_id: &str,
+        plot_bounds: PlotBounds,
+        [width, height]: [usize; 2],
+        persistence: &Persistence,
+        points: &[[f64; 2]],
+    ) -> (egui::TextureId, PlotBounds) {
+        let acquisition = self.display_point.or_else(|| self.display_end());
+        let state = self
+            .persistence
+            .entry(line_id.to_string())
+            .or_insert_with(|| PersistenceState {
+                buffer: PersistenceBuffer::new(plot_bounds, width, height, persistence.decay),
+                acquired_at: None,
+                texture: Default::default(),
+            });
+        let decay = persistence.decay.clamp(0.0, 0.999);
+        if !state.buffer.matches(plot_bounds, width, height) || state.buffer.decay() != decay {
+            state.buffer = PersistenceBuffer::new(plot_bounds, width, height, persistence.decay);
+            state.acquired_at = None;
+        }
+        if state.acquired_at != acquisition || state.acquired_at.is_none() {
+            state.buffer.add_acquisition(points);
+            state.acquired_at = acquisition;
+        }
+
+        let image = state.buffer.to_image(persistence.colormap);
+        let name = format!("{:?} persistence {}", self.id, line_id);
+        let texture_id = state.texture.update(ctx, &name, image);
+        (texture_id, state.buffer.bounds())
+    }
+
+    /// Returns the persistence buffer of the given line, if persistence is
+    /// enabled for the plot.
+    pub fn persistence(&self, line_id: &str) -> Option<&PersistenceBuffer> {
+        self.persistence.get(line_id).map(|s| &s.buffer)
+    }
+
+    /// Discard the persistence buffers of all lines.
+    pub fn clear_persistence(&mut self) {
+        self.persistence.clear();
+    }
+
+    /// Returns a texture showing how many points of the given line fall into
+    /// each pixel of the given plot bounds, for a plot section of `width` ×
+    /// `height` pixels. The texture is only updated when the bounds, size or
+    /// data change. Sections are numbered from 0, see [crate::ViewMode::Sweep].
+    ///
+    /// Called by [crate::TimeseriesPlot] for lines drawn as
+    /// [crate::RenderMode::Density].
+    pub(crate) fn plot_density(
+        &mut self,
+        ctx: &egui::Context,
+        line_id: &str,
+        section: usize,
+        plot_bounds: PlotBounds,
+        size: [usize; 2],
+        colormap: Colormap,
+    ) -> Option<egui::TextureId> {
+        let lines = match self.stopped_at {
+            Some(_) => &self.frozen,
+            None => &self.lines,
+        };
+        let line = lines.get(line_id)?;
+        let size = [size[0].max(1), size[1].max(1)];
+        let key = (plot_bounds, size, colormap, line.revision, line.end());
+        let state = self
+            .density
+            .entry((line_id.to_string(), section))
+            .or_default();
+        if state.key != Some(key) {
+            let counts = line.density(plot_bounds, size);
+            let name = format!("{:?} density {} {}", self.id, line_id, section);
+            state
+                .texture
+                .update(ctx, &name, to_image(&counts, size, colormap));
+            state.key = Some(key);
+        }
+        state.texture.id()
+    }
+
+    /// Returns the band to be drawn for the given line as `[x, min, mean,
+    /// max]`, or None if the line should be drawn as usual at this zoom level.
+    ///
+    /// Called by [crate::TimeseriesPlot] for lines drawn as
+    /// [crate::RenderMode::Band].
+    pub(crate) fn plot_band(
+        &mut self,
+        line_id: &str,
+        plot_bounds: PlotBounds,
+        width: f32,
+    ) -> Option<Vec<[f64; 4]>> {
+        let points_per_pixel = self.points_per_pixel;
+        self.displayed_line_mut(line_id)?
+            .plot_band(plot_bounds, width, points_per_pixel)
+    }
+
+    /// Set the acquisition mode that is used when a trigger is set.
+    pub fn set_acquisition_mode(&mut self, acquisition_mode: AcquisitionMode) {
+        if self.acquisition_mode != acquisition_mode {
+            self.acquisition_mode = acquisition_mode;
+            if acquisition_mode == AcquisitionMode::Single {
+                self.single_armed_at = self.end();
+            }
+        }
+    }
+
+    pub fn acquisition_mode(&self) -> AcquisitionMode {
+        self.acquisition_mode
+    }
+
+    /// Resume updating the view. In [AcquisitionMode::Single], this arms the
+    /// trigger for the next event.
+    pub fn run(&mut self) {
+        self.stopped_at = None;
+        self.frozen.clear();
+        if self.acquisition_mode == AcquisitionMode::Single {
+            self.single_armed_at = self.end();
+        }
+    }
+
+    /// Freeze the view. New data is still added to the caches, but not shown
+    /// until [TimeseriesPlotMemory::run] is called.
+    ///
+    /// The data around the view is copied, so it is kept even if it is
+    /// removed from the source in the meantime. This covers two view widths
+    /// on either side, to leave some room for panning and zooming out.
+    pub fn stop(&mut self) {
+        if self.stopped_at.is_some() {
+            return;
+        }
+        let end = self.end().unwrap_or(f64::NEG_INFINITY);
+        self.stopped_at = Some(end);
+
+        let anchor = self.display_point.unwrap_or(end);
+        let margin = 2.0 * self.last_view_width;
+        let range = anchor - margin..=f64::min(anchor + margin, end);
+        self.frozen = self
+            .lines
+            .iter()
+            .map(|(id, line)| (id.clone(), line.snapshot(range.clone(), 0.0)))
+            .collect();
+    }
+
+    /// The given line as shown, i.e. the copy made when the acquisition was
+    /// stopped, if it is.
+    fn displayed_line(&self, line_id: &str) -> Option<&TimeseriesLineMemory<X, Y>> {
+        match self.stopped_at {
+            Some(_) => self.frozen.get(line_id),
+            None => self.lines.get(line_id),
+        }
+    }
+
+    fn displayed_line_mut(&mut self, line_id: &str) -> Option<&mut TimeseriesLineMemory<X, Y>> {
+        match self.stopped_at {
+            Some(_) => self.frozen.get_mut(line_id),
+            None => self.lines.get_mut(line_id),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stopped_at.is_none()
+    }
+
+    /// Returns the end of the data that is shown, i.e. the end of the data at
+    /// the time the acquisition was stopped, or the current end otherwise.
+    pub fn display_end(&self) -> Option<f64> {
+        self.stopped_at.or_else(|| self.end())
+    }
+
+    /// Returns the X axis value of the most recent trigger event, if any.
+    pub fn trigger_point(&self) -> Option<f64> {
+        self.trigger_state.as_ref().and_then(|s| s.last_trigger)
+    }
+
+    /// Returns the value of the given line at `x`, linearly interpolated
+    /// between the neighbouring raw data points. Returns None outside of the
+    /// data and within gaps.
+    pub fn value_at(&self, line_id: &str, x: f64) -> Option<f64> {
+        self.displayed_line(line_id).and_then(|l| l.value_at(x))
+    }
+
+    /// Computes a measurement for the given line over the given range on the
+    /// X axis. The raw data is used, independent of the zoom level. Returns
+    /// None if there is no data in range, or not enough of it for the
+    /// measurement, e.g. less than two rising edges for a frequency.
+    pub fn measure(
+        &self,
+        line_id: &str,
+        range: RangeInclusive<f64>,
+        measurement: Measurement,
+    ) -> Option<f64> {
+        let points = self.displayed_line(line_id)?.raw_points();
+        let i_begin = points.partition_point(|p| p.0 < *range.start());
+        let i_end = points.partition_point(|p| p.0 <= *range.end());
+        measurement.compute(&points[i_begin..i_end.max(i_begin)])
+    }
+
+    /// Returns the positions of the two time cursors on the X axis, as
+    /// displayed, i.e. relative to the trigger event when triggered.
+    pub fn time_cursors(&self) -> Option<[f64; 2]> {
+        self.cursors.time
+    }
+
+    /// Move the time cursors. If set to None while they are shown, they are
+    /// placed in the current view again.
+    pub fn set_time_cursors(&mut self, time_cursors: Option<[f64; 2]>) {
+        self.cursors.time = time_cursors;
+    }
+
+    /// Returns the positions of the two level cursors on the Y axis.
+    pub fn value_cursors(&self) -> Option<[f64; 2]> {
+        self.cursors.value
+    }
+
+    /// Move the level cursors. If set to None while they are shown, they are
+    /// placed in the current view again.
+    pub fn set_value_cursors(&mut self, value_cursors: Option<[f64; 2]>) {
+        self.cursors.value = value_cursors;
+    }
+
+    /// Returns the current last known X axis value, if any are present.
     pub fn end(&self) -> Option<f64> {
         let line_maxes: Vec<f64> = self.lines.values().filter_map(|l| l.end()).collect();
-        (line_maxes.len() > 0).then_some(
+        (!line_maxes.is_empty()).then_some(
             line_maxes
                 .iter()
                 .fold(f64::NEG_INFINITY, |a, b| f64::max(a, *b)),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic noise on top of a slow sine, with slightly irregular
+    // sample spacing.
+    fn noisy_line(method: DownsamplingMethod, len: usize) -> TimeseriesLineMemory<f64, f64> {
+        let mut line = TimeseriesLineMemory::new(method);
+        let mut state = 1u64;
+        let mut noise = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+        };
+        let points: Vec<(f64, Option<f64>)> = (0..len)
+            .map(|i| {
+                let x = i as f64 * 1e-3 + noise() * 1e-4;
+                (x, Some((x * 3.0).sin() + noise()))
+            })
+            .collect();
+        for chunk in points.chunks(997) {
+            line.extend_points(chunk.iter().copied());
+        }
+        line
+    }
+
+    #[test]
+    fn incremental_extension_matches_full_build() {
+        for method in [
+            DownsamplingMethod::MinMax,
+            DownsamplingMethod::Mean,
+            DownsamplingMethod::Lttb,
+            DownsamplingMethod::M4,
+        ] {
+            // Built in chunks of 997 points.
+            let incremental = noisy_line(method, 100_000);
+            let mut full = TimeseriesLineMemory::<f64, f64>::new(method);
+            full.extend_points(incremental.raw_points().iter().map(|(x, y)| (*x, Some(*y))));
+
+            assert_eq!(incremental.cache_levels.len(), full.cache_levels.len());
+            for (a, b) in incremental.cache_levels.iter().zip(&full.cache_levels) {
+                assert_eq!(a.points, b.points, "{method:?}");
+                assert_eq!(a.bucket_ends, b.bucket_ends, "{method:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn eviction_trims_every_level() {
+        let mut line = noisy_line(DownsamplingMethod::MinMax, 100_000);
+        line.evict_front(12_345);
+        line.extend_points((0..5000).map(|i| (100.0 + i as f64 * 1e-3, Some(0.0))));
+        line.evict_front(7_000);
+
+        let raw = line.raw_points();
+        let extent = |points: &[(f64, f64)]| {
+            points
+                .iter()
+                .fold([f64::INFINITY, f64::NEG_INFINITY], |[min, max], p| {
+                    [min.min(p.1), max.max(p.1)]
+                })
+        };
+        assert!(line.cache_levels.len() > 2);
+        for level in &line.cache_levels[1..] {
+            // Nothing from before the evicted data is left, and the extremes
+            // of the remaining data are kept at every level.
+            assert!(level.points[0].0 >= raw[0].0);
+            assert_eq!(level.points.last().unwrap().0, raw.last().unwrap().0);
+            assert_eq!(extent(&level.points), extent(raw));
+        }
+    }
+
+    #[test]
+    fn lttb_keeps_one_point_per_bucket() {
+        let line = noisy_line(DownsamplingMethod::Lttb, 100_000);
+        for pair in line.cache_levels.windows(2) {
+            let (source, level) = (&pair[0].points, &pair[1].points);
+            assert_eq!(level.len(), source.len().div_ceil(DOWNSAMPLING_BUCKET_SIZE));
+            // Every kept point is one of the points of its bucket.
+            for (bucket, point) in level.iter().enumerate() {
+                let range = pair[1].bucket_range(bucket, source.len());
+                assert!(source[range].contains(point));
+            }
+            assert_eq!(level[0], source[0]);
+        }
+    }
+
+    // Points compared by their bits, so gaps compare equal.
+    fn bits(points: &[(f64, f64)]) -> Vec<[u64; 2]> {
+        points
+            .iter()
+            .map(|p| [p.0.to_bits(), p.1.to_bits()])
+            .collect()
+    }
+
+    #[test]
+    fn gaps_keep_the_data_around_them() {
+        // A dropout every 50 samples, and a glitch right before one of them.
+        let points: Vec<(f64, Option<f64>)> = (0..200_000)
+            .map(|i| {
+                let y = match i {
+                    _ if i % 50 == 49 => None,
+                    123_448 => Some(10.0),
+                    _ => Some((i as f64 * 1e-3).sin()),
+                };
+                (i as f64 * 1e-3, y)
+            })
+            .collect();
+        let bounds = PlotBounds::from_min_max([0.0, -2.0], [200.0, 2.0]);
+        let finite = |ys: &mut dyn Iterator<Item = f64>| {
+            ys.fold((0, 0, f64::NEG_INFINITY), |(finite, total, max), y| {
+                (finite + usize::from(!y.is_nan()), total + 1, max.max(y))
+            })
+        };
+
+        for method in [
+            DownsamplingMethod::MinMax,
+            DownsamplingMethod::Mean,
+            DownsamplingMethod::Lttb,
+            DownsamplingMethod::M4,
+        ] {
+            let mut line = TimeseriesLineMemory::<f64, f64>::new(method);
+            for chunk in points.chunks(997) {
+                line.extend_points(chunk.iter().copied());
+            }
+            let keeps_extremes =
+                matches!(method, DownsamplingMethod::MinMax | DownsamplingMethod::M4);
+
+            assert!(line.cache_levels.len() > 2);
+            for level in &line.cache_levels[1..] {
+                let (n, total, max) = finite(&mut level.points.iter().map(|p| p.1));
+                assert!(3 * n > total, "{method:?}: {n} of {total} points");
+                assert!(!keeps_extremes || max == 10.0, "{method:?}");
+                assert_eq!(level.bucket_ends.last(), Some(&total));
+            }
+
+            // Built at once, the levels are the same as built in chunks.
+            let mut full = TimeseriesLineMemory::<f64, f64>::new(method);
+            full.extend_points(points.iter().copied());
+            assert_eq!(line.cache_levels.len(), full.cache_levels.len());
+            for (a, b) in line.cache_levels.iter().zip(&full.cache_levels) {
+                assert!(bits(&a.points) == bits(&b.points), "{method:?}");
+                assert_eq!(a.bucket_ends, b.bucket_ends, "{method:?}");
+            }
+
+            let plotted = line.plot(bounds, 500.0, DEFAULT_POINTS_PER_PIXEL);
+            let (n, total, max) = finite(&mut plotted.iter().map(|p| p[1]));
+            assert!(3 * n > total, "{method:?}: {n} of {total} points");
+            assert!(!keeps_extremes || max == 10.0, "{method:?}");
+        }
+    }
+
+    #[test]
+    fn gap_thresholds_interrupt_the_line() {
+        // Samples every millisecond, with dropouts of 20 samples early on,
+        // before the median interval is known, and every 10 seconds.
+        let dropouts: Vec<usize> = std::iter::once(40)
+            .chain((0..10).map(|k| 5000 + k * 10_000))
+            .collect();
+        let points: Vec<(f64, Option<f64>)> = (0..100_000)
+            .filter(|i| !dropouts.iter().any(|d| (*d..d + 20).contains(i)))
+            .map(|i| {
+                let x = i as f64 * 1e-3;
+                (x, Some((x * 3.0).sin()))
+            })
+            .collect();
+        // The X axis range of each dropout, between the samples around it.
+        let ranges: Vec<(f64, f64)> = dropouts
+            .iter()
+            .map(|d| ((d - 1) as f64 * 1e-3, (d + 20) as f64 * 1e-3))
+            .collect();
+        let in_dropout = |x: f64| {
+            ranges
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&x))
+        };
+        let bounds = PlotBounds::from_min_max([0.0, -2.0], [100.0, 2.0]);
+
+        for threshold in [GapThreshold::Fixed(5e-3), GapThreshold::MedianInterval(5.0)] {
+            let build = |chunk_len: usize| {
+                let mut line = TimeseriesLineMemory::<f64, f64>::new(DownsamplingMethod::MinMax);
+                line.gap_threshold = threshold;
+                for chunk in points.chunks(chunk_len) {
+                    line.extend_points(chunk.iter().copied());
+                }
+                line
+            };
+
+            // Gaps inserted once the median interval is known end up the same
+            // as if it had been known from the start.
+            let (mut incremental, full) = (build(37), build(points.len()));
+            assert_eq!(incremental.cache_levels.len(), full.cache_levels.len());
+            for (a, b) in incremental.cache_levels.iter().zip(&full.cache_levels) {
+                assert!(bits(&a.points) == bits(&b.points), "{threshold:?}");
+                assert_eq!(a.bucket_ends, b.bucket_ends, "{threshold:?}");
+            }
+
+            let gaps: Vec<f64> = incremental
+                .raw_points()
+                .iter()
+                .filter(|p| p.1.is_nan())
+                .map(|p| p.0)
+                .collect();
+            let midpoints: Vec<f64> = ranges
+                .iter()
+                .map(|(start, end)| (start + end) / 2.0)
+                .collect();
+            assert_eq!(gaps, midpoints, "{threshold:?}");
+
+            // The downsampled line is interrupted at every dropout, and never
+            // connects points across one.
+            let plotted = incremental.plot(bounds, 500.0, DEFAULT_POINTS_PER_PIXEL);
+            assert!(plotted.len() < points.len() / 10);
+            let plotted_gaps: Vec<f64> = plotted
+                .iter()
+                .filter(|p| p[1].is_nan())
+                .map(|p| p[0])
+                .collect();
+            assert_eq!(plotted_gaps.len(), dropouts.len(), "{threshold:?}");
+            assert!(plotted_gaps.iter().all(|x| in_dropout(*x)), "{threshold:?}");
+            for pair in plotted
+                .windows(2)
+                .filter(|w| !w[0][1].is_nan() && !w[1][1].is_nan())
+            {
+                let crosses = ranges
+                    .iter()
+                    .any(|(start, end)| pair[0][0] <= *start && pair[1][0] >= *end);
+                assert!(!crosses, "{threshold:?}: {pair:?}");
+            }
+        }
+
+        // Without a threshold, only missing values interrupt the line.
+        let mut line = TimeseriesLineMemory::<f64, f64>::new(DownsamplingMethod::MinMax);
+        line.extend_points(points.iter().copied());
+        assert!(line.raw_points().iter().all(|p| !p.1.is_nan()));
+    }
+
+    // First, minimum, maximum and last value of the polyline in every pixel
+    // column of the view.
+    fn column_extents(
+        points: &[[f64; 2]],
+        bounds: PlotBounds,
+        width: usize,
+    ) -> Vec<Option<[f64; 4]>> {
+        let (x_min, column_width) = (bounds.min()[0], bounds.width() / width as f64);
+        let mut extents: Vec<Option<[f64; 4]>> = vec![None; width];
+        for [x, y] in points {
+            let column = ((x - x_min) / column_width).floor();
+            if (0.0..width as f64).contains(&column) {
+                let extent = extents[column as usize].get_or_insert([*y; 4]);
+                extent[1] = extent[1].min(*y);
+                extent[2] = extent[2].max(*y);
+                extent[3] = *y;
+            }
+        }
+        extents
+    }
+
+    #[test]
+    fn set_origin_shifts_cached_f64_data() {
+        let id = "a".to_string();
+        let mut memory = TimeseriesPlotMemory::<f64, f64>::new("origin");
+        let data: Vec<(f64, Option<f64>)> = (0..60)
+            .map(|i| (100.0 + i as f64, Some(i as f64)))
+            .collect();
+        memory.update_cache(&id, data[..50].iter().cloned());
+
+        // Without an origin, the values are drawn as they are.
+        assert_eq!(memory.display_end(), Some(149.0));
+        memory.set_origin(120.0);
+        assert_eq!(memory.display_end(), Some(29.0));
+        assert_eq!(memory.value_at(&id, -20.0), Some(0.0));
+
+        // New data continues where the shifted data ends.
+        memory.update_cache(&id, data.iter().cloned());
+        let raw = memory.lines[&id].raw_points();
+        assert!(raw.windows(2).all(|w| w[1].0 - w[0].0 == 1.0));
+        assert_eq!(raw[0].0, -20.0);
+        assert_eq!(memory.display_end(), Some(39.0));
+
+        memory.set_origin(150.0);
+        assert_eq!(memory.value_at(&id, -50.0), Some(0.0));
+        assert_eq!(memory.display_end(), Some(9.0));
+    }
+
+    #[test]
+    fn set_origin_shifts_cached_instant_data() {
+        use std::time::{Duration, Instant};
+
+        let id = "a".to_string();
+        let mut memory = TimeseriesPlotMemory::<Instant, f64>::new("origin");
+        let start = Instant::now();
+        let data: Vec<(Instant, Option<f64>)> = (0..60)
+            .map(|i| (start + Duration::from_millis(100 * i), Some(i as f64)))
+            .collect();
+        let close = |a: Option<f64>, b: f64| a.is_some_and(|a| (a - b).abs() < 1e-9);
+
+        // The first sample is the origin, unless set.
+        memory.update_cache(&id, data[..50].iter().cloned());
+        assert_eq!(memory.origin(), Some(&start));
+        assert!(close(memory.display_end(), 4.9));
+
+        memory.set_origin(start + Duration::from_secs(2));
+        assert!(close(memory.display_end(), 2.9));
+        assert!(close(memory.value_at(&id, -2.0), 0.0));
+
+        memory.update_cache(&id, data.iter().cloned());
+        let raw = memory.lines[&id].raw_points();
+        assert!(raw.windows(2).all(|w| (w[1].0 - w[0].0 - 0.1).abs() < 1e-9));
+        assert!(close(memory.display_end(), 3.9));
+
+        memory.set_origin(start);
+        assert!(close(memory.display_end(), 5.9));
+        assert!(close(memory.value_at(&id, 0.0), 0.0));
+    }
+
+    #[test]
+    fn stop_keeps_data_evicted_from_the_source() {
+        let id = "a".to_string();
+        let mut memory = TimeseriesPlotMemory::<f64, f64>::new("stop");
+        let (mut ring, mut samples) = (VecDeque::new(), 0);
+        let mut frame = |memory: &mut TimeseriesPlotMemory<f64, f64>| {
+            for _ in 0..100 {
+                let x = samples as f64 * 1e-2;
+                ring.push_back((x, Some(x.sin())));
+                samples += 1;
+            }
+            while ring.len() > 1000 {
+                ring.pop_front();
+            }
+            memory.update_cache(&id, ring.iter().cloned());
+        };
+
+        for _ in 0..20 {
+            frame(&mut memory);
+        }
+        memory.stop();
+        let end = memory.display_end().unwrap();
+        let view = PlotBounds::from_min_max([end - 5.0, -1.0], [end, 1.0]);
+        let frozen = memory.plot(&id, view, 100.0);
+        assert!(!frozen.is_empty());
+
+        for _ in 0..20 {
+            frame(&mut memory);
+        }
+        assert_eq!(memory.display_end(), Some(end));
+        assert_eq!(memory.plot(&id, view, 100.0), frozen);
+        assert!(memory.value_at(&id, end - 1.0).is_some());
+
+        memory.run();
+        assert!(memory.display_end().unwrap() > end);
+    }
+
+    #[test]
+    fn math_lines_follow_their_sources() {
+        let (a, b, m) = ("a".to_string(), "b".to_string(), "m".to_string());
+        let mut memory = TimeseriesPlotMemory::<f64, f64>::new("math");
+        memory.set_math_line(&m, MathFunction::difference(&a, &b));
+
+        // b is sampled halfway between the samples of a at twice their value,
+        // so interpolating it gives a - b = -x. Both are ring buffers that
+        // grow at different rates.
+        let (mut a_ring, mut b_ring) = (VecDeque::new(), VecDeque::new());
+        for frame in 0..100 {
+            for _ in 0..37 {
+                let x = a_ring
+                    .back()
+                    .map_or(0.0, |p: &(f64, Option<f64>)| p.0 + 1.0);
+                a_ring.push_back((x, Some(x)));
+            }
+            for _ in 0..(frame % 3) * 29 {
+                let x = b_ring
+                    .back()
+                    .map_or(-0.5, |p: &(f64, Option<f64>)| p.0 + 1.0);
+                b_ring.push_back((x, Some(2.0 * x)));
+            }
+            while a_ring.len() > 1000 {
+                a_ring.pop_front();
+            }
+            while b_ring.len() > 1000 {
+                b_ring.pop_front();
+            }
+            memory.update_cache(&a, a_ring.iter().cloned());
+            memory.update_cache(&b, b_ring.iter().cloned());
+            memory.update_math_lines();
+
+            // Points of a are only computed once b has caught up with them,
+            // and dropped along with them.
+            let b_end = b_ring.back().map_or(f64::NEG_INFINITY, |p| p.0);
+            let expected: Vec<(f64, f64)> = a_ring
+                .iter()
+                .map(|(x, _)| (*x, -x))
+                .filter(|(x, _)| *x <= b_end)
+                .collect();
+            assert_eq!(
+                memory.lines[&m].raw_points(),
+                &expected[..],
+                "frame {frame}"
+            );
+        }
+    }
+
+    #[test]
+    fn math_lines_are_recomputed_when_a_source_is_rebuilt() {
+        let (a, m) = ("a".to_string(), "m".to_string());
+        let mut memory = TimeseriesPlotMemory::<f64, f64>::new("math");
+        memory.set_math_line(&m, MathFunction::integral(&a));
+        let constant = |y: f64| (0..101).map(move |i| (i as f64 * 0.5, Some(y)));
+
+        memory.update_cache(&a, constant(1.0));
+        memory.update_math_lines();
+        let points = memory.lines[&m].raw_points();
+        assert_eq!(points.len(), 101);
+        assert!(points.iter().all(|(x, y)| y == x));
+
+        // Same X axis values but different samples, so the source is rebuilt
+        // and the integral starts over.
+        memory.update_cache(&a, constant(2.0));
+        memory.update_math_lines();
+        let points = memory.lines[&m].raw_points();
+        assert_eq!(points.len(), 101);
+        assert!(points.iter().all(|(x, y)| *y == 2.0 * x));
+    }
+
+    #[test]
+    fn mask_counts_trigger_events_only() {
+        let id = "a".to_string();
+        let mask = Mask::new(&id).upper([(0.0, 2.0), (0.5, 2.0)]);
+        for (level, acquisitions) in [(5.0, 0..=0), (0.0, 3..=5)] {
+            let trigger = Trigger::edge(&id, level, Slope::Rising);
+            let mut memory = TimeseriesPlotMemory::<f64, f64>::new("mask");
+            let mut data = Vec::new();
+            for _ in 0..50 {
+                for _ in 0..100 {
+                    let x = data.len() as f64 * 1e-3;
+                    data.push((x, Some((x * std::f64::consts::TAU).sin())));
+                }
+                memory.update_cache(&id, data.iter().cloned());
+                memory.acquire(&trigger, 1.0);
+                memory.update_mask(&mask);
+            }
+            let counts = memory.mask_counts();
+            assert_eq!(counts.failed, 0);
+            assert!(acquisitions.contains(&counts.passed), "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn segments_store_every_trigger_event() {
+        let id = "a".to_string();
+        let trigger = Trigger::edge(&id, 0.0, Slope::Rising);
+        let mut memory = TimeseriesPlotMemory::<f64, f64>::new("segments");
+        memory.set_segment_capacity(25);
+
+        // Ten periods per frame, each rising through zero just after a
+        // multiple of 0.1.
+        let mut data = Vec::new();
+        let mut frame = |memory: &mut TimeseriesPlotMemory<f64, f64>| {
+            for _ in 0..1000 {
+                let x = data.len() as f64 * 1e-3;
+                let y = ((x - 5e-5) * 10.0 * std::f64::consts::TAU).sin();
+                data.push((x, Some(y)));
+            }
+            memory.update_cache(&id, data.iter().cloned());
+            memory.acquire(&trigger, 0.05);
+            memory.update_segments(0.01, 0.05);
+        };
+        let trigger_points = |memory: &TimeseriesPlotMemory<f64, f64>| -> Vec<f64> {
+            (0..memory.segment_count())
+                .filter_map(|i| memory.segment_trigger_point(i))
+                .collect()
+        };
+
+        frame(&mut memory);
+        assert_eq!(memory.segment_count(), 10);
+        for _ in 0..4 {
+            frame(&mut memory);
+        }
+        // Only the newest events are kept, without skipping any of them.
+        let points = trigger_points(&memory);
+        assert_eq!(points.len(), 25);
+        assert!(points.windows(2).all(|w| (w[1] - w[0] - 0.1).abs() < 1e-6));
+        assert_eq!(Some(*points.last().unwrap()), memory.trigger_point());
+
+        // The selection follows its segment as older ones are dropped, and
+        // moves on to the oldest remaining one once its own is dropped.
+        memory.select_segment(Some(20));
+        frame(&mut memory);
+        assert_eq!(memory.selected_segment(), Some(10));
+        assert_eq!(memory.segment_trigger_point(10), Some(points[20]));
+        memory.select_segment(Some(3));
+        frame(&mut memory);
+        assert_eq!(memory.selected_segment(), Some(0));
+        memory.set_segment_capacity(0);
+        assert_eq!(memory.segment_count(), 0);
+        assert_eq!(memory.selected_segment(), None);
+    }
+
+    #[test]
+    fn absolute_mask_counts_each_capture_once() {
+        let id = "a".to_string();
+        let mask = Mask::new(&id)
+            .reference(MaskReference::Absolute)
+            .upper([(0.5, 2.0), (0.8, 2.0)]);
+        let mut memory = TimeseriesPlotMemory::<f64, f64>::new("mask");
+        for capture in 0..4 {
+            // Every capture replaces the previous one, with the same X axis
+            // values, and arrives in two parts.
+            let data: Vec<(f64, Option<f64>)> = (0..1000)
+                .map(|i| {
+                    let x = i as f64 * 1e-3;
+                    let y = match (capture, i) {
+                        (2, 600) => 3.0,
+                        _ => x.sin() + capture as f64 * 0.1,
+                    };
+                    (x, Some(y))
+                })
+                .collect();
+            for end in [500, 1000] {
+                memory.update_cache(&id, data[..end].iter().cloned());
+                memory.update_mask(&mask);
+                memory.update_mask(&mask);
+            }
+        }
+        assert_eq!(
+            memory.mask_counts(),
+            MaskCounts {
+                passed: 3,
+                failed: 1
+            }
+        );
+    }
+
+    #[test]
+    fn point_budget_per_method() {
+        let bounds = PlotBounds::from_min_max([0.0, -2.0], [100.0, 2.0]);
+        for (method, points_per_column) in [
+            (DownsamplingMethod::MinMax, 2),
+            (DownsamplingMethod::Mean, 1),
+            (DownsamplingMethod::Lttb, 1),
+            (DownsamplingMethod::M4, 4),
+        ] {
+            let mut line = noisy_line(method, 200_000);
+            let plotted = line.plot(bounds, 1000.0, DEFAULT_POINTS_PER_PIXEL);
+            // Give or take a few points for the columns at the edges.
+            let expected = 1000 * points_per_column;
+            assert!(
+                plotted.len().abs_diff(expected) <= expected / 100,
+                "{method:?}: {} points",
+                plotted.len()
+            );
+        }
+    }
+
+    #[test]
+    fn density_follows_the_raw_data() {
+        let rows = |counts: Vec<u32>, width: usize| -> Vec<u32> {
+            counts.chunks(width).map(|row| row.iter().sum()).collect()
+        };
+
+        // Every point in view is counted, even with millions of them.
+        let line = noisy_line(DownsamplingMethod::MinMax, 2_500_000);
+        let bounds = PlotBounds::from_min_max([-1.0, -2.0], [2500.0, 2.0]);
+        let total: u32 = line.density(bounds, [100, 40]).iter().sum();
+        assert_eq!(total, 2_500_000);
+
+        // A signal with a period of ten samples, stepping through ten values,
+        // puts the same number of points into each of ten rows. Binning only
+        // every n-th point would alias with the period.
+        let mut line = TimeseriesLineMemory::<f64, f64>::new(DownsamplingMethod::MinMax);
+        line.extend_points((0..2_000_000).map(|i| {
+            let y = (i % 10) as f64 / 10.0 - 0.45;
+            (i as f64 * 1e-3, Some(y))
+        }));
+        let bounds = PlotBounds::from_min_max([0.0, -0.5], [2000.0, 0.5]);
+        assert_eq!(
+            rows(line.density(bounds, [100, 10]), 100),
+            vec![200_000; 10]
+        );
+    }
+
+    #[test]
+    fn band_follows_the_downsampling_method() {
+        let mut line = noisy_line(DownsamplingMethod::MinMax, 200_000);
+        let bounds = PlotBounds::from_min_max([50.0, -2.0], [150.0, 2.0]);
+        let in_view = |rows: &[[f64; 4]]| -> Vec<[f64; 4]> {
+            rows.iter()
+                .filter(|r| (50.0..=150.0).contains(&r[0]))
+                .copied()
+                .collect()
+        };
+
+        // One row per column, spanning the extremes of the raw data.
+        let band = line
+            .plot_band(bounds, 500.0, DEFAULT_POINTS_PER_PIXEL)
+            .unwrap();
+        let rows = in_view(&band);
+        assert!(rows.len().abs_diff(500) <= 1, "{} rows", rows.len());
+        assert!(rows.iter().all(|r| r[1] <= r[2] && r[2] <= r[3]));
+        let raw: Vec<f64> = line
+            .raw_points()
+            .iter()
+            .filter(|p| (50.0..=150.0).contains(&p.0))
+            .map(|p| p.1)
+            .collect();
+        let min = |values: &mut dyn Iterator<Item = f64>| values.fold(f64::INFINITY, f64::min);
+        let max = |values: &mut dyn Iterator<Item = f64>| values.fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(
+            min(&mut rows.iter().map(|r| r[1])),
+            min(&mut raw.iter().copied())
+        );
+        assert_eq!(
+            max(&mut rows.iter().map(|r| r[3])),
+            max(&mut raw.iter().copied())
+        );
+
+        // Switching the method recomputes the band for the same view.
+        line.set_downsampling_method(DownsamplingMethod::Mean);
+        let mean = line
+            .plot_band(bounds, 500.0, DEFAULT_POINTS_PER_PIXEL)
+            .unwrap();
+        assert_ne!(mean, band);
+
+        // Zoomed in to the raw data, or without downsampling, the line is
+        // drawn as is.
+        let zoomed = PlotBounds::from_min_max([50.0, -2.0], [50.2, 2.0]);
+        assert!(line
+            .plot_band(zoomed, 500.0, DEFAULT_POINTS_PER_PIXEL)
+            .is_none());
+        line.set_downsampling_method(DownsamplingMethod::None);
+        assert!(line
+            .plot_band(bounds, 500.0, DEFAULT_POINTS_PER_PIXEL)
+            .is_none());
+        // Along with a neighbour on either side, and the first and last point.
+        let plotted = line.plot(bounds, 500.0, DEFAULT_POINTS_PER_PIXEL);
+        assert_eq!(plotted.len(), raw.len() + 4);
+    }
+
+    #[test]
+    fn m4_is_pixel_exact() {
+        let mut line = noisy_line(DownsamplingMethod::M4, 400_000);
+        line.evict_front(12_345);
+        assert!(line.cache_levels.len() > 2);
+
+        for (x_min, x_max, width) in [(0.0, 400.0, 1000), (13.7, 291.3, 777), (100.1, 180.9, 1900)]
+        {
+            let bounds = PlotBounds::from_min_max([x_min, -2.0], [x_max, 2.0]);
+            let plotted = line.plot(bounds, width as f32, DEFAULT_POINTS_PER_PIXEL);
+            let raw: Vec<[f64; 2]> = line.raw_points().iter().map(|(x, y)| [*x, *y]).collect();
+            let expected = column_extents(&raw, bounds, width);
+            let differing = column_extents(&plotted, bounds, width)
+                .iter()
+                .zip(&expected)
+                .filter(|(a, b)| a != b)
+                .count();
+            assert_eq!(differing, 0, "view {x_min}..{x_max} at {width} px");
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use egui::ColorImage;
+use egui_plot::PlotBounds;
+
+use crate::colormap::Colormap;
+use crate::texture::CachedTexture;
+
+/// Settings for the persistence view, see [crate::TimeseriesPlot::persistence].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Persistence {
+    pub(crate) decay: f32,
+    pub(crate) colormap: Colormap,
+    pub(crate) hide_lines: bool,
+}
+
+impl Persistence {
+    /// Every acquisition fades by the given factor with each following one,
+    /// e.g. 0.9 keeps traces visible for a few dozen acquisitions.
+    pub fn new(decay: f32) -> Self {
+        Self {
+            decay,
+            colormap: Colormap::default(),
+            hide_lines: false,
+        }
+    }
+
+    pub fn colormap(mut self, colormap: Colormap) -> Self {
+        self.colormap = colormap;
+        self
+    }
+
+    /// Only draw the persistence view, instead of drawing the lines on top.
+    pub fn hide_lines(mut self, hide_lines: bool) -> Self {
+        self.hide_lines = hide_lines;
+        self
+    }
+}
+
+/// Intensity buffer over time×value bins, in which every acquisition leaves
+/// a trace that fades out over the following ones.
+///
+/// Everything is computed on the CPU and deterministic, so the same
+/// acquisitions always result in the same buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PersistenceBuffer {
+    bounds: PlotBounds,
+    width: usize,
+    height: usize,
+    decay: f32,
+    intensity: Vec<f32>,
+}
+
+impl PersistenceBuffer {
+    /// Create an empty buffer of `width` × `height` bins covering the given
+    /// plot bounds. The decay is clamped to the range from 0 to 0.999.
+    pub fn new(bounds: PlotBounds, width: usize, height: usize, decay: f32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        Self {
+            bounds,
+            width,
+            height,
+            decay: decay.clamp(0.0, 0.999),
+            intensity: vec![0.0; width * height],
+        }
+    }
+
+    pub fn bounds(&self) -> PlotBounds {
+        self.bounds
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    /// Returns the number of bins in X and Y direction.
+    pub fn size(&self) -> [usize; 2] {
+        [self.width, self.height]
+    }
+
+    /// Whether the buffer covers about the same bounds with the same number
+    /// of bins, i.e. every bound is off by less than half a bin.
+    pub(crate) fn matches(&self, bounds: PlotBounds, width: usize, height: usize) -> bool {
+        let bin = [
+            self.bounds.width() / self.width as f64,
+            self.bounds.height() / self.height as f64,
+        ];
+        let close = |a: [f64; 2], b: [f64; 2]| (0..2).all(|i| (a[i] - b[i]).abs() < bin[i] / 2.0);
+        self.size() == [width.max(1), height.max(1)]
+            && close(self.bounds.min(), bounds.min())
+            && close(self.bounds.max(), bounds.max())
+    }
+
+    /// Fades the buffer and adds the line through the given points. Every
+    /// bin the line passes through is counted once. Gaps are marked by NaN
+    /// values.
+    pub fn add_acquisition(&mut self, points: &[[f64; 2]]) {
+        let mut hits = vec![false; self.intensity.len()];
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let to_bins = |p: &[f64; 2]| {
+            [
+                (p[0] - min[0]) / (max[0] - min[0]) * self.width as f64,
+                (max[1] - p[1]) / (max[1] - min[1]) * self.height as f64,
+            ]
+        };
+
+        let mut hit = |[column, row]: [f64; 2]| {
+            if (0.0..self.width as f64).contains(&column)
+                && (0.0..self.height as f64).contains(&row)
+            {
+                hits[row as usize * self.width + column as usize] = true;
+            }
+        };
+        for pair in points.windows(2) {
+            if pair[0][1].is_nan() || pair[1][1].is_nan() {
+                continue;
+            }
+            let (from, to) = (to_bins(&pair[0]), to_bins(&pair[1]));
+            let steps = f64::max((to[0] - from[0]).abs(), (to[1] - from[1]).abs())
+                .ceil()
+                .clamp(1.0, (self.width + self.height) as f64) as usize;
+            for step in 0..=steps {
+                let t = step as f64 / steps as f64;
+                hit([
+                    from[0] + (to[0] - from[0]) * t,
+                    from[1] + (to[1] - from[1]) * t,
+                ]);
+            }
+        }
+        if let [point] = points {
+            if !point[1].is_nan() {
+                hit(to_bins(point));
+            }
+        }
+
+        for (intensity, hit) in self.intensity.iter_mut().zip(hits) {
+            *intensity = *intensity * self.decay + if hit { 1.0 } else { 0.0 };
+        }
+    }
+
+    /// Returns the intensity of a bin between 0 and 1, where 1 means the bin
+    /// has been hit by every acquisition for a long time. Row 0 is at the
+    /// top.
+    pub fn intensity(&self, column: usize, row: usize) -> f32 {
+        self.intensity
+            .get(row * self.width + column)
+            .map_or(0.0, |i| (i * (1.0 - self.decay)).min(1.0))
+    }
+
+    pub fn to_image(&self, colormap: Colormap) -> ColorImage {
+        let pixels = (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |column| (column, row)))
+            .map(|(column, row)| colormap.color(self.intensity(column, row)))
+            .collect();
+        ColorImage {
+            size: [self.width, self.height],
+            pixels,
+        }
+    }
+}
+
+/// Persistence buffer of a line, along with its texture.
+#[derive(Debug)]
+pub(crate) struct PersistenceState {
+    pub(crate) buffer: PersistenceBuffer,
+    // Identifies the last added acquisition.
+    pub(crate) acquired_at: Option<f64>,
+    pub(crate) texture: CachedTexture,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(decay: f32) -> PersistenceBuffer {
+        PersistenceBuffer::new(
+            PlotBounds::from_min_max([0.0, 0.0], [10.0, 10.0]),
+            10,
+            10,
+            decay,
+        )
+    }
+
+    #[test]
+    fn decays_with_every_acquisition() {
+        let mut buffer = buffer(0.5);
+        // A horizontal line through the fourth row from the top.
+        buffer.add_acquisition(&[[0.0, 6.5], [10.0, 6.5]]);
+        assert_eq!(buffer.intensity(0, 3), 0.5);
+        assert_eq!(buffer.intensity(9, 3), 0.5);
+        assert_eq!(buffer.intensity(0, 4), 0.0);
+
+        buffer.add_acquisition(&[[0.0, 1.5], [10.0, 1.5]]);
+        assert_eq!(buffer.intensity(0, 3), 0.25);
+        assert_eq!(buffer.intensity(0, 8), 0.5);
+
+        // Bins hit by every acquisition approach full intensity.
+        for _ in 0..50 {
+            buffer.add_acquisition(&[[0.0, 6.5], [10.0, 6.5]]);
+        }
+        assert!(buffer.intensity(5, 3) > 0.999);
+        assert!(buffer.intensity(5, 8) < 1e-6);
+    }
+
+    #[test]
+    fn counts_each_bin_once_per_acquisition() {
+        let mut buffer = buffer(0.0);
+        // Many points within the same bin, and a steep segment through a
+        // whole column.
+        let points: Vec<[f64; 2]> = (0..100).map(|i| [0.5 + i as f64 * 1e-3, 0.5]).collect();
+        buffer.add_acquisition(&points);
+        assert_eq!(buffer.intensity(0, 9), 1.0);
+
+        buffer.add_acquisition(&[[5.5, 0.0], [5.6, 10.0]]);
+        assert_eq!(buffer.intensity(0, 9), 0.0);
+        assert!((0..10).all(|row| buffer.intensity(5, row) == 1.0));
+    }
+
+    #[test]
+    fn skips_gaps() {
+        let mut buffer = buffer(0.0);
+        buffer.add_acquisition(&[[0.5, 0.5], [4.5, f64::NAN], [9.5, 0.5]]);
+        assert!((0..10).all(|column| buffer.intensity(column, 9) == 0.0));
+    }
+}
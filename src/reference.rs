@@ -0,0 +1,95 @@
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// First line of a saved reference.
+const HEADER: &str = "x,y";
+
+/// Writes the points of a reference as CSV, with gaps written as NaN.
+pub(crate) fn save_points<P: AsRef<Path>>(
+    path: P,
+    points: impl Iterator<Item = (f64, f64)>,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+    writeln!(writer, "{}", HEADER)?;
+    for (x, y) in points {
+        writeln!(writer, "{},{}", x, y)?;
+    }
+    writer.flush()
+}
+
+/// Reads points written by [save_points].
+pub(crate) fn load_points<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<(f64, f64)>> {
+    let invalid = |line: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid reference point: {:?}", line),
+        )
+    };
+
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let mut points = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line == HEADER {
+            continue;
+        }
+        let (x, y) = line.split_once(',').ok_or_else(|| invalid(line))?;
+        let x = x.trim().parse().map_err(|_| invalid(line))?;
+        let y = y.trim().parse().map_err(|_| invalid(line))?;
+        points.push((x, y));
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A file in the temporary directory that is removed again when dropped.
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("egui_oszi-{}-{}.csv", std::process::id(), name);
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn points_survive_a_round_trip() {
+        let file = TempFile::new("round-trip");
+        let points = [(-0.5, 1.25), (0.0, f64::NAN), (1e-9, -3.0), (2.0, 1e300)];
+        save_points(&file.0, points.iter().copied()).unwrap();
+
+        let text = std::fs::read_to_string(&file.0).unwrap();
+        assert_eq!(text.lines().next(), Some(HEADER));
+
+        let loaded = load_points(&file.0).unwrap();
+        assert_eq!(loaded.len(), points.len());
+        for (loaded, point) in loaded.iter().zip(&points) {
+            assert_eq!(loaded.0, point.0);
+            assert!(loaded.1 == point.1 || loaded.1.is_nan() && point.1.is_nan());
+        }
+    }
+
+    #[test]
+    fn invalid_points_are_rejected() {
+        let file = TempFile::new("invalid");
+        for text in ["x,y\n0,1\n2\n", "x,y\n0,one\n", "0,1\n1,2,3\n"] {
+            std::fs::write(&file.0, text).unwrap();
+            let error = load_points(&file.0).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "{text:?}");
+        }
+
+        // The header is optional, and blank lines are skipped.
+        std::fs::write(&file.0, "0,1\n\n 1 , 2 \n").unwrap();
+        assert_eq!(load_points(&file.0).unwrap(), [(0.0, 1.0), (1.0, 2.0)]);
+    }
+}
@@ -0,0 +1,70 @@
+use egui::{Button, Response, Ui, Widget};
+
+use crate::cursors::format_value;
+use crate::{TimeseriesPlotMemory, TimeseriesXAxis};
+
+/// Controls to step through the segments stored in a [TimeseriesPlotMemory],
+/// see [TimeseriesPlotMemory::set_segment_capacity].
+pub struct SegmentBrowser<'mem, X, Y> {
+    memory: &'mem mut TimeseriesPlotMemory<X, Y>,
+}
+
+impl<'mem, X, Y> SegmentBrowser<'mem, X, Y> {
+    pub fn new(memory: &'mem mut TimeseriesPlotMemory<X, Y>) -> Self {
+        Self { memory }
+    }
+}
+
+impl<
+        'mem,
+        X: TimeseriesXAxis,
+        Y: Default + num_traits::Float + num_traits::float::TotalOrder + Into<f64>,
+    > Widget for SegmentBrowser<'mem, X, Y>
+{
+    fn ui(self, ui: &mut Ui) -> Response {
+        let memory = self.memory;
+        ui.horizontal(|ui| {
+            let count = memory.segment_count();
+            let selected = memory.selected_segment();
+
+            // Stepping back from the current acquisition starts at the newest
+            // segment, stepping forward from the newest segment returns to it.
+            let previous = Button::new("⏴");
+            if ui
+                .add_enabled(count > 0 && selected != Some(0), previous)
+                .clicked()
+            {
+                memory.select_segment(Some(selected.map_or(count - 1, |i| i - 1)));
+            }
+            if ui
+                .add_enabled(selected.is_some(), Button::new("⏵"))
+                .clicked()
+            {
+                memory.select_segment(selected.map(|i| i + 1).filter(|i| *i < count));
+            }
+
+            let selected = memory.selected_segment();
+            ui.label(match selected {
+                Some(i) => format!(
+                    "Segment {}/{} at {}",
+                    i + 1,
+                    count,
+                    format_value(memory.segment_trigger_point(i), None),
+                ),
+                None => format!("Live, {} segments", count),
+            });
+
+            if ui.selectable_label(selected.is_none(), "Live").clicked() {
+                memory.select_segment(None);
+            }
+            let mut overlay = memory.overlay_segments();
+            if ui.checkbox(&mut overlay, "Overlay all").changed() {
+                memory.set_overlay_segments(overlay);
+            }
+            if ui.button("Clear").clicked() {
+                memory.clear_segments();
+            }
+        })
+        .response
+    }
+}
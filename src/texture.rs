@@ -0,0 +1,100 @@
+use std::ops::RangeInclusive;
+
+use egui::{Color32, ColorImage, Context, Pos2, Rect, Shape, TextureHandle, TextureId, Ui};
+use egui_plot::{PlotBounds, PlotGeometry, PlotItem, PlotPoint, PlotTransform};
+
+/// A texture that is uploaded once and updated in place afterwards.
+#[derive(Default)]
+pub(crate) struct CachedTexture(Option<TextureHandle>);
+
+impl std::fmt::Debug for CachedTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CachedTexture")
+            .field(&self.0.as_ref().map(|t| t.id()))
+            .finish()
+    }
+}
+
+impl CachedTexture {
+    pub(crate) fn update(&mut self, ctx: &Context, name: &str, image: ColorImage) -> TextureId {
+        let options = egui::TextureOptions::NEAREST;
+        match &mut self.0 {
+            Some(handle) => handle.set(image, options),
+            None => self.0 = Some(ctx.load_texture(name, image, options)),
+        }
+        self.0.as_ref().unwrap().id()
+    }
+
+    pub(crate) fn id(&self) -> Option<TextureId> {
+        self.0.as_ref().map(|t| t.id())
+    }
+}
+
+/// An image stretched over the given plot bounds. Unlike
+/// [egui_plot::PlotImage], it doesn't affect the automatic plot bounds, so it
+/// can cover the whole view without the view growing every frame.
+pub(crate) struct ImageItem {
+    texture_id: TextureId,
+    bounds: PlotBounds,
+    name: String,
+    color: Color32,
+}
+
+impl ImageItem {
+    pub(crate) fn new(texture_id: TextureId, bounds: PlotBounds) -> Self {
+        Self {
+            texture_id,
+            bounds,
+            name: String::new(),
+            color: Color32::TRANSPARENT,
+        }
+    }
+
+    /// Shows the image under the legend entry of the line it is drawn for, so
+    /// hiding the line hides the image as well.
+    pub(crate) fn legend(mut self, name: Option<&str>, color: Color32) -> Self {
+        self.name = name.unwrap_or_default().to_owned();
+        self.color = color;
+        self
+    }
+}
+
+impl PlotItem for ImageItem {
+    fn shapes(&self, _ui: &Ui, transform: &PlotTransform, shapes: &mut Vec<Shape>) {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let rect = Rect::from_two_pos(
+            transform.position_from_point(&PlotPoint::new(min[0], max[1])),
+            transform.position_from_point(&PlotPoint::new(max[0], min[1])),
+        );
+        let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        shapes.push(Shape::image(self.texture_id, rect, uv, Color32::WHITE));
+    }
+
+    fn initialize(&mut self, _x_range: RangeInclusive<f64>) {}
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn color(&self) -> Color32 {
+        self.color
+    }
+
+    fn highlight(&mut self) {}
+
+    fn highlighted(&self) -> bool {
+        false
+    }
+
+    fn geometry(&self) -> PlotGeometry<'_> {
+        PlotGeometry::None
+    }
+
+    fn bounds(&self) -> PlotBounds {
+        PlotBounds::NOTHING
+    }
+
+    fn id(&self) -> Option<egui::Id> {
+        None
+    }
+}
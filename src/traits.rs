@@ -6,22 +6,49 @@ use std::fmt::Debug;
 //{}
 
 pub trait TimeseriesXAxis: Clone + Debug + PartialOrd + PartialEq + Sized {
+    /// Type of the distance between two values, e.g. [std::time::Duration]
+    /// for [std::time::Instant].
+    type Difference;
+
+    /// Converts the value to a plot coordinate relative to `origin`. Values
+    /// before the origin result in negative coordinates.
+    ///
+    /// Implementations may initialize the origin if it is not set yet.
     fn to_f64(self, origin: &mut Option<Self>) -> f64;
+
+    /// Converts a distance between two values to plot units.
+    fn difference_to_f64(difference: Self::Difference) -> f64;
 }
 
 impl TimeseriesXAxis for std::time::Instant {
+    type Difference = std::time::Duration;
+
     fn to_f64(self, origin: &mut Option<Self>) -> f64 {
         if let Some(origin) = origin {
-            (self - *origin).as_secs_f64()
+            if self >= *origin {
+                (self - *origin).as_secs_f64()
+            } else {
+                -(*origin - self).as_secs_f64()
+            }
         } else {
-            origin.replace(self.clone());
+            origin.replace(self);
             0.0
         }
     }
+
+    fn difference_to_f64(difference: Self::Difference) -> f64 {
+        difference.as_secs_f64()
+    }
 }
 
 impl TimeseriesXAxis for f64 {
-    fn to_f64(self, _origin: &mut Option<Self>) -> f64 {
-        self
+    type Difference = f64;
+
+    fn to_f64(self, origin: &mut Option<Self>) -> f64 {
+        self - origin.unwrap_or(0.0)
+    }
+
+    fn difference_to_f64(difference: Self::Difference) -> f64 {
+        difference
     }
 }
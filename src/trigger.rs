@@ -0,0 +1,233 @@
+/// Direction of the edge a [Trigger] reacts to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Slope {
+    #[default]
+    Rising,
+    Falling,
+    Both,
+}
+
+/// How the view reacts to trigger events, like the acquisition modes of an
+/// oscilloscope.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AcquisitionMode {
+    /// Show the most recent trigger event, and free-run showing the newest
+    /// data if there hasn't been one for a while.
+    #[default]
+    Auto,
+    /// Show the most recent trigger event, holding it until the next one.
+    /// Nothing is shown until the first one.
+    Normal,
+    /// Wait for the next trigger event, show it and stop.
+    Single,
+}
+
+/// Trigger configuration for a [crate::TimeseriesPlot].
+///
+/// When a trigger is set, the view is pinned to the most recent trigger
+/// event, with the X axis showing the distance to it. This keeps periodic
+/// signals standing still instead of scrolling past.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trigger {
+    pub(crate) line_id: String,
+    pub(crate) level: f64,
+    pub(crate) slope: Slope,
+    pub(crate) holdoff: f64,
+    pub(crate) position: f64,
+}
+
+impl Trigger {
+    /// Trigger whenever the given line crosses `level` in the direction
+    /// given by `slope`.
+    pub fn edge(line_id: impl ToString, level: f64, slope: Slope) -> Self {
+        Self {
+            line_id: line_id.to_string(),
+            level,
+            slope,
+            holdoff: 0.0,
+            position: 0.0,
+        }
+    }
+
+    /// Minimum distance between two trigger events, in plot units (i.e.
+    /// seconds for [std::time::Instant]). Crossings within this distance
+    /// after a trigger event are ignored.
+    pub fn holdoff(mut self, holdoff: f64) -> Self {
+        self.holdoff = holdoff;
+        self
+    }
+
+    /// Horizontal position of the trigger event in the view, as a fraction of
+    /// the view width from the left edge. For instance, `0.1` shows some
+    /// history before the event, while `0.5` centers it. Defaults to `0.0`.
+    pub fn position(mut self, position: f64) -> Self {
+        self.position = position.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Amount of data shown before and after the trigger event for a view of
+    /// the given width.
+    pub(crate) fn pre_post_trigger(&self, view_width: f64) -> (f64, f64) {
+        let pre_trigger = view_width * self.position;
+        (pre_trigger, view_width - pre_trigger)
+    }
+
+    /// Returns the interpolated X position at which the line crosses the
+    /// trigger level between the two points, if it does so in the right
+    /// direction.
+    fn crossing<Y: Into<f64> + Copy>(&self, a: (f64, Y), b: (f64, Y)) -> Option<f64> {
+        let (y_a, y_b) = (a.1.into(), b.1.into());
+        let rising = y_a < self.level && y_b >= self.level;
+        let falling = y_a > self.level && y_b <= self.level;
+        let matches = match self.slope {
+            Slope::Rising => rising,
+            Slope::Falling => falling,
+            Slope::Both => rising || falling,
+        };
+
+        // NaN values never compare, so gaps never trigger.
+        matches.then(|| a.0 + (self.level - y_a) / (y_b - y_a) * (b.0 - a.0))
+    }
+}
+
+/// Trigger events found so far for a plot.
+#[derive(Debug)]
+pub(crate) struct TriggerState {
+    pub(crate) trigger: Trigger,
+    pub(crate) last_trigger: Option<f64>,
+    scanned_until: f64,
+}
+
+impl TriggerState {
+    pub(crate) fn new(trigger: Trigger) -> Self {
+        Self {
+            trigger,
+            last_trigger: None,
+            scanned_until: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Moves all known trigger events by `delta` on the X axis, e.g. after
+    /// the origin of the X axis has changed.
+    pub(crate) fn shift_x(&mut self, delta: f64) {
+        self.last_trigger = self.last_trigger.map(|t| t + delta);
+        self.scanned_until += delta;
+    }
+
+    /// Looks for new trigger events in `points`, which are expected to be
+    /// sorted by their X value. Only events followed by at least `post_trigger`
+    /// of data are accepted, so the view after the event is complete. Returns
+    /// all new events, oldest first.
+    pub(crate) fn update<Y: Into<f64> + Copy>(
+        &mut self,
+        points: &[(f64, Y)],
+        post_trigger: f64,
+    ) -> Vec<f64> {
+        let mut events = Vec::new();
+        let Some(end) = points.last().map(|p| p.0 - post_trigger) else {
+            return events;
+        };
+        if end <= self.scanned_until {
+            return events;
+        }
+
+        // Start with the pair of points around where we stopped last time.
+        let start = points
+            .partition_point(|p| p.0 < self.scanned_until)
+            .saturating_sub(1);
+        for pair in points[start..].windows(2) {
+            if pair[0].0 > end {
+                break;
+            }
+            let Some(x) = self.trigger.crossing(pair[0], pair[1]) else {
+                continue;
+            };
+            if x > end {
+                break;
+            }
+
+            let holdoff_end = self
+                .last_trigger
+                .map_or(f64::NEG_INFINITY, |t| t + self.trigger.holdoff);
+            if x > self.scanned_until && x >= holdoff_end {
+                self.last_trigger = Some(x);
+                events.push(x);
+            }
+        }
+
+        self.scanned_until = end;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sawtooth rising from -0.5 to 0.5 within every unit, so it crosses zero
+    // upwards at every half.
+    fn sawtooth(until: f64) -> Vec<(f64, f64)> {
+        (0..)
+            .map(|i| i as f64 * 0.03)
+            .take_while(|x| *x < until)
+            .map(|x| (x, x.fract() - 0.5))
+            .collect()
+    }
+
+    fn last_trigger(trigger: Trigger, points: &[(f64, f64)]) -> Option<f64> {
+        let mut state = TriggerState::new(trigger);
+        state.update(points, 1.0);
+        state.last_trigger
+    }
+
+    #[test]
+    fn finds_interpolated_crossings() {
+        let points = sawtooth(10.0);
+        let rising = Trigger::edge("a", 0.0, Slope::Rising);
+        assert!((last_trigger(rising.clone(), &points).unwrap() - 8.5).abs() < 1e-9);
+
+        // The sawtooth only falls at full units, between two samples.
+        let falling = Trigger::edge("a", 0.0, Slope::Falling);
+        let last = last_trigger(falling, &points).unwrap();
+        assert!((last - last.round()).abs() < 0.03, "{last}");
+
+        // Searching incrementally finds the same events.
+        let mut state = TriggerState::new(rising);
+        for end in 1..=points.len() {
+            state.update(&points[..end], 1.0);
+        }
+        assert!((state.last_trigger.unwrap() - 8.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn returns_every_new_event() {
+        let points = sawtooth(10.0);
+        let mut state = TriggerState::new(Trigger::edge("a", 0.0, Slope::Rising));
+        let first = state.update(&points[..points.len() / 2], 1.0);
+        let second = state.update(&points, 1.0);
+        let events: Vec<f64> = first.into_iter().chain(second).collect();
+        assert_eq!(events.len(), 9);
+        for (i, x) in events.iter().enumerate() {
+            assert!((x - (i as f64 + 0.5)).abs() < 1e-9, "{events:?}");
+        }
+        assert!(state.update(&points, 1.0).is_empty());
+    }
+
+    #[test]
+    fn holdoff_skips_events() {
+        // Events at 0.5, 3.5 and 6.5, with the ones in between held off.
+        let trigger = Trigger::edge("a", 0.0, Slope::Rising).holdoff(2.5);
+        let last = last_trigger(trigger, &sawtooth(10.0)).unwrap();
+        assert!((last - 6.5).abs() < 1e-9, "{last}");
+    }
+
+    #[test]
+    fn gaps_never_trigger() {
+        let mut points = sawtooth(10.0);
+        let i = points.partition_point(|p| p.0 < 8.5);
+        points[i].1 = f64::NAN;
+        let trigger = Trigger::edge("a", 0.0, Slope::Rising);
+        let last = last_trigger(trigger, &points).unwrap();
+        assert!((last - 7.5).abs() < 1e-9, "{last}");
+    }
+}